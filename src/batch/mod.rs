@@ -0,0 +1,176 @@
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::comparator::Comparator;
+use crate::model::{
+    ImageComparisonOptions, ImageComparisonResult, ImageComparisonState, RenderStyle,
+};
+use image::ImageFormat;
+
+/// An expected image, the actual image with the same file name and their comparison.
+#[derive(Debug)]
+pub struct ComparisonPair {
+    /// File name shared by both images, or of the actual image for `ComparisonPair::compare`.
+    pub name: String,
+    pub expected_image: PathBuf,
+    pub actual_image: PathBuf,
+    pub result: ImageComparisonResult,
+    /// Rendered differences, once written with `BatchResult::write_diff_images` or by
+    /// `compare_directories` with a diff directory.
+    pub diff_image: Option<PathBuf>,
+}
+
+impl ComparisonPair {
+    /// Compares two images like `compare_images_with_options`.
+    pub fn compare(
+        expected_image: &Path,
+        actual_image: &Path,
+        options: &ImageComparisonOptions,
+    ) -> Self {
+        // a single comparison has nothing to cache
+        let result: ImageComparisonResult = Comparator::with_cache_capacity(options.clone(), 0)
+            .compare(expected_image.as_os_str(), actual_image.as_os_str());
+        Self {
+            name: file_name(actual_image),
+            expected_image: expected_image.to_path_buf(),
+            actual_image: actual_image.to_path_buf(),
+            result,
+            diff_image: None,
+        }
+    }
+    pub fn is_match(&self) -> bool {
+        self.result.image_comparison_state == ImageComparisonState::Match
+    }
+}
+
+/// Comparisons of all images of an expected directory with an actual directory.
+#[derive(Debug)]
+pub struct BatchResult {
+    pub expected_directory: PathBuf,
+    pub actual_directory: PathBuf,
+    /// Pairs sorted by file name.
+    pub pairs: Vec<ComparisonPair>,
+    /// Expected images without an actual image of the same file name.
+    pub missing_actual_images: Vec<PathBuf>,
+    /// Actual images without an expected image of the same file name.
+    pub unexpected_actual_images: Vec<PathBuf>,
+}
+
+impl BatchResult {
+    pub fn match_count(&self) -> usize {
+        self.pairs.iter().filter(|pair| pair.is_match()).count()
+    }
+    pub fn mismatch_count(&self) -> usize {
+        self.pairs.len() - self.match_count()
+    }
+    /// Whether all pairs match and every image has a counterpart.
+    pub fn is_success(&self) -> bool {
+        self.mismatch_count() == 0
+            && self.missing_actual_images.is_empty()
+            && self.unexpected_actual_images.is_empty()
+    }
+    /// Renders the differences of the mismatching pairs with `RenderStyle::Outline` into
+    /// `directory`, as PNG files named after the pairs, and records their paths.
+    pub fn write_diff_images(&mut self, directory: &Path) -> io::Result<()> {
+        fs::create_dir_all(directory)?;
+        for pair in self.pairs.iter_mut() {
+            write_diff_image(pair, directory)?;
+        }
+        Ok(())
+    }
+}
+
+// pairs whose render data was dropped keep the diff image written before
+fn write_diff_image(pair: &mut ComparisonPair, directory: &Path) -> io::Result<()> {
+    if let Some(diff_image) = pair.result.render(RenderStyle::Outline) {
+        let diff_image_path: PathBuf = directory.join(Path::new(&pair.name).with_extension("png"));
+        diff_image
+            .save_with_format(&diff_image_path, ImageFormat::Png)
+            .map_err(|error| io::Error::new(io::ErrorKind::Other, error))?;
+        pair.diff_image = Some(diff_image_path);
+    }
+    Ok(())
+}
+
+/// Compares every file of `expected_directory` with the file of the same name in
+/// `actual_directory`, with the same options for all pairs.
+///
+/// Subdirectories and files without an image extension are skipped. Files in only one of
+/// the directories are listed in the result and not compared.
+///
+/// With `diff_directory` the differences of each mismatching pair are written like with
+/// `BatchResult::write_diff_images` right after its comparison, and the images of the
+/// comparison are dropped, so `ImageComparisonResult::render` returns `None` for the pairs.
+/// Without it every mismatching pair keeps its images until the result is dropped.
+pub fn compare_directories(
+    expected_directory: &Path,
+    actual_directory: &Path,
+    options: &ImageComparisonOptions,
+    diff_directory: Option<&Path>,
+) -> io::Result<BatchResult> {
+    let expected_names: Vec<OsString> = list_file_names(expected_directory)?;
+    let actual_names: Vec<OsString> = list_file_names(actual_directory)?;
+    if let Some(diff_directory) = diff_directory {
+        fs::create_dir_all(diff_directory)?;
+    }
+    // every expected image is compared once, so only the buffers are reused
+    let mut comparator: Comparator = Comparator::with_cache_capacity(options.clone(), 0);
+    let mut pairs: Vec<ComparisonPair> = Vec::new();
+    let mut missing_actual_images: Vec<PathBuf> = Vec::new();
+    for name in expected_names.iter() {
+        let expected_image: PathBuf = expected_directory.join(name);
+        if !actual_names.contains(name) {
+            missing_actual_images.push(expected_image);
+            continue;
+        }
+        let actual_image: PathBuf = actual_directory.join(name);
+        let result: ImageComparisonResult =
+            comparator.compare(expected_image.as_os_str(), actual_image.as_os_str());
+        let mut pair: ComparisonPair = ComparisonPair {
+            name: name.to_string_lossy().into_owned(),
+            expected_image,
+            actual_image,
+            result,
+            diff_image: None,
+        };
+        if let Some(diff_directory) = diff_directory {
+            write_diff_image(&mut pair, diff_directory)?;
+            pair.result.render_data = None;
+        }
+        pairs.push(pair);
+    }
+    let unexpected_actual_images: Vec<PathBuf> = actual_names
+        .iter()
+        .filter(|name| !expected_names.contains(name))
+        .map(|name| actual_directory.join(name))
+        .collect();
+    Ok(BatchResult {
+        expected_directory: expected_directory.to_path_buf(),
+        actual_directory: actual_directory.to_path_buf(),
+        pairs,
+        missing_actual_images,
+        unexpected_actual_images,
+    })
+}
+
+fn list_file_names(directory: &Path) -> io::Result<Vec<OsString>> {
+    let mut names: Vec<OsString> = Vec::new();
+    for entry in fs::read_dir(directory)? {
+        let entry: fs::DirEntry = entry?;
+        if entry.file_type()?.is_file() && ImageFormat::from_path(entry.path()).is_ok() {
+            names.push(entry.file_name());
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+// the file name of a path, or the whole path without one
+pub(crate) fn file_name(path: &Path) -> String {
+    match path.file_name() {
+        Some(name) => name.to_string_lossy().into_owned(),
+        None => path.to_string_lossy().into_owned(),
+    }
+}
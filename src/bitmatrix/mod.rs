@@ -0,0 +1,93 @@
+// one bit per pixel, rows are stored one after another without padding
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct BitMatrix {
+    width: usize,
+    height: usize,
+    words: Vec<u64>,
+}
+
+impl BitMatrix {
+    pub(crate) fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            words: vec![0; (width * height + 63) / 64],
+        }
+    }
+    pub(crate) fn width(&self) -> usize {
+        self.width
+    }
+    pub(crate) fn height(&self) -> usize {
+        self.height
+    }
+    pub(crate) fn get(&self, x: usize, y: usize) -> bool {
+        let index: usize = y * self.width + x;
+        self.words[index / 64] & (1u64 << (index % 64)) != 0
+    }
+    pub(crate) fn set(&mut self, x: usize, y: usize, value: bool) {
+        let index: usize = y * self.width + x;
+        if value {
+            self.words[index / 64] |= 1u64 << (index % 64);
+        } else {
+            self.words[index / 64] &= !(1u64 << (index % 64));
+        }
+    }
+    pub(crate) fn clear(&mut self) {
+        self.words.fill(0);
+    }
+    pub(crate) fn count_ones(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+    // coordinates of the set bits in raster order, skipping empty words
+    pub(crate) fn iter_ones(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let width: usize = self.width;
+        self.words
+            .iter()
+            .enumerate()
+            .filter(|(_, word)| **word != 0)
+            .flat_map(move |(word_index, word)| {
+                let word: u64 = *word;
+                (0..64)
+                    .filter(move |bit| word & (1u64 << bit) != 0)
+                    .map(move |bit| {
+                        let index: usize = word_index * 64 + bit;
+                        (index % width, index / width)
+                    })
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BitMatrix;
+    #[test]
+    fn bit_matrix_set_and_get() {
+        let mut bit_matrix: BitMatrix = BitMatrix::new(10, 7);
+        assert_eq!(0, bit_matrix.count_ones());
+        bit_matrix.set(9, 6, true);
+        bit_matrix.set(3, 6, true);
+        bit_matrix.set(0, 0, true);
+        bit_matrix.set(0, 0, false);
+        assert!(bit_matrix.get(9, 6));
+        assert!(bit_matrix.get(3, 6));
+        assert!(!bit_matrix.get(0, 0));
+        assert!(!bit_matrix.get(8, 6));
+        assert_eq!(2, bit_matrix.count_ones());
+        bit_matrix.clear();
+        assert_eq!(0, bit_matrix.count_ones());
+    }
+    #[test]
+    fn bit_matrix_iter_ones_in_raster_order() {
+        let mut bit_matrix: BitMatrix = BitMatrix::new(100, 3);
+        bit_matrix.set(70, 0, true);
+        bit_matrix.set(5, 1, true);
+        bit_matrix.set(99, 2, true);
+        assert_eq!(
+            vec![(70, 0), (5, 1), (99, 2)],
+            bit_matrix.iter_ones().collect::<Vec<(usize, usize)>>()
+        );
+    }
+}
@@ -0,0 +1,248 @@
+use crate::model::ColorDifference;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct Lab {
+    pub(crate) l: f64,
+    pub(crate) a: f64,
+    pub(crate) b: f64,
+}
+
+impl Lab {
+    pub(crate) fn new(l: f64, a: f64, b: f64) -> Self {
+        Self { l, a, b }
+    }
+    // sRGB (D65) -> linear RGB -> CIE XYZ -> CIELAB
+    pub(crate) fn from_srgb(rgb: &[u8]) -> Self {
+        let r: f64 = srgb_to_linear(rgb[0]);
+        let g: f64 = srgb_to_linear(rgb[1]);
+        let b: f64 = srgb_to_linear(rgb[2]);
+        let x: f64 = (0.4124564 * r + 0.3575761 * g + 0.1804375 * b) / 0.95047;
+        let y: f64 = 0.2126729 * r + 0.7151522 * g + 0.0721750 * b;
+        let z: f64 = (0.0193339 * r + 0.119192 * g + 0.9503041 * b) / 1.08883;
+        let fx: f64 = lab_f(x);
+        let fy: f64 = lab_f(y);
+        let fz: f64 = lab_f(z);
+        Self::new(116f64 * fy - 16f64, 500f64 * (fx - fy), 200f64 * (fy - fz))
+    }
+}
+
+fn srgb_to_linear(value: u8) -> f64 {
+    let value: f64 = value as f64 / 255f64;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn lab_f(t: f64) -> f64 {
+    let delta: f64 = 6f64 / 29f64;
+    if t > delta.powi(3) {
+        t.cbrt()
+    } else {
+        t / (3f64 * delta * delta) + 4f64 / 29f64
+    }
+}
+
+pub(crate) fn delta_e_76(first: &Lab, second: &Lab) -> f64 {
+    ((first.l - second.l).powi(2) + (first.a - second.a).powi(2) + (first.b - second.b).powi(2))
+        .sqrt()
+}
+
+// graphic arts weighting: kL = 1, K1 = 0.045, K2 = 0.015
+pub(crate) fn delta_e_94(first: &Lab, second: &Lab) -> f64 {
+    let delta_l: f64 = first.l - second.l;
+    let c1: f64 = first.a.hypot(first.b);
+    let c2: f64 = second.a.hypot(second.b);
+    let delta_c: f64 = c1 - c2;
+    let delta_a: f64 = first.a - second.a;
+    let delta_b: f64 = first.b - second.b;
+    let delta_h_squared: f64 =
+        (delta_a * delta_a + delta_b * delta_b - delta_c * delta_c).max(0f64);
+    let s_c: f64 = 1f64 + 0.045 * c1;
+    let s_h: f64 = 1f64 + 0.015 * c1;
+    (delta_l * delta_l + (delta_c / s_c).powi(2) + delta_h_squared / (s_h * s_h)).sqrt()
+}
+
+pub(crate) fn ciede2000(first: &Lab, second: &Lab) -> f64 {
+    let pow_25_7: f64 = 25f64.powi(7);
+    let c1: f64 = first.a.hypot(first.b);
+    let c2: f64 = second.a.hypot(second.b);
+    let c_mean: f64 = (c1 + c2) / 2f64;
+    let g: f64 = 0.5 * (1f64 - (c_mean.powi(7) / (c_mean.powi(7) + pow_25_7)).sqrt());
+    let a1: f64 = (1f64 + g) * first.a;
+    let a2: f64 = (1f64 + g) * second.a;
+    let c1: f64 = a1.hypot(first.b);
+    let c2: f64 = a2.hypot(second.b);
+    let h1: f64 = hue_angle(first.b, a1);
+    let h2: f64 = hue_angle(second.b, a2);
+
+    let delta_l: f64 = second.l - first.l;
+    let delta_c: f64 = c2 - c1;
+    let delta_h_angle: f64 = if c1 * c2 == 0f64 {
+        0f64
+    } else if (h2 - h1).abs() <= 180f64 {
+        h2 - h1
+    } else if h2 - h1 > 180f64 {
+        h2 - h1 - 360f64
+    } else {
+        h2 - h1 + 360f64
+    };
+    let delta_h: f64 = 2f64 * (c1 * c2).sqrt() * (delta_h_angle.to_radians() / 2f64).sin();
+
+    let l_mean: f64 = (first.l + second.l) / 2f64;
+    let c_mean: f64 = (c1 + c2) / 2f64;
+    let h_mean: f64 = if c1 * c2 == 0f64 {
+        h1 + h2
+    } else if (h1 - h2).abs() <= 180f64 {
+        (h1 + h2) / 2f64
+    } else if h1 + h2 < 360f64 {
+        (h1 + h2 + 360f64) / 2f64
+    } else {
+        (h1 + h2 - 360f64) / 2f64
+    };
+    let t: f64 = 1f64 - 0.17 * (h_mean - 30f64).to_radians().cos()
+        + 0.24 * (2f64 * h_mean).to_radians().cos()
+        + 0.32 * (3f64 * h_mean + 6f64).to_radians().cos()
+        - 0.20 * (4f64 * h_mean - 63f64).to_radians().cos();
+    let delta_theta: f64 = 30f64 * (-((h_mean - 275f64) / 25f64).powi(2)).exp();
+    let r_c: f64 = 2f64 * (c_mean.powi(7) / (c_mean.powi(7) + pow_25_7)).sqrt();
+    let s_l: f64 =
+        1f64 + (0.015 * (l_mean - 50f64).powi(2)) / (20f64 + (l_mean - 50f64).powi(2)).sqrt();
+    let s_c: f64 = 1f64 + 0.045 * c_mean;
+    let s_h: f64 = 1f64 + 0.015 * c_mean * t;
+    let r_t: f64 = -(2f64 * delta_theta).to_radians().sin() * r_c;
+    ((delta_l / s_l).powi(2)
+        + (delta_c / s_c).powi(2)
+        + (delta_h / s_h).powi(2)
+        + r_t * (delta_c / s_c) * (delta_h / s_h))
+        .sqrt()
+}
+
+fn hue_angle(b: f64, a: f64) -> f64 {
+    if a == 0f64 && b == 0f64 {
+        return 0f64;
+    }
+    let angle: f64 = b.atan2(a).to_degrees();
+    if angle < 0f64 {
+        angle + 360f64
+    } else {
+        angle
+    }
+}
+
+pub(crate) fn delta_e(color_difference: ColorDifference, e: &[u8], a: &[u8]) -> f64 {
+    let first: Lab = Lab::from_srgb(e);
+    let second: Lab = Lab::from_srgb(a);
+    match color_difference {
+        ColorDifference::Rgb | ColorDifference::DeltaE76 => delta_e_76(&first, &second),
+        ColorDifference::DeltaE94 => delta_e_94(&first, &second),
+        ColorDifference::Ciede2000 => ciede2000(&first, &second),
+    }
+}
+
+// color delta is the ΔE for the CIELAB formulas and the largest channel difference otherwise
+pub(crate) fn color_delta(color_difference: ColorDifference, e: &[u8; 4], a: &[u8; 4]) -> f64 {
+    if color_difference != ColorDifference::Rgb {
+        return delta_e(color_difference, e, a);
+    }
+    e.iter()
+        .zip(a.iter())
+        .map(|(e, a)| e.abs_diff(*a))
+        .max()
+        .unwrap_or(0) as f64
+}
+
+pub(crate) fn max_color_delta(color_difference: ColorDifference) -> f64 {
+    match color_difference {
+        ColorDifference::Rgb => 255f64,
+        _ => 100f64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ciede2000, delta_e_76, delta_e_94, Lab};
+    fn assert_close(expected: f64, actual: f64) {
+        assert!(
+            (expected - actual).abs() < 0.0001,
+            "expected: {} actual: {}",
+            expected,
+            actual
+        );
+    }
+    #[test]
+    fn lab_from_srgb_white_and_black() {
+        let white: Lab = Lab::from_srgb(&[255, 255, 255]);
+        assert_close(100f64, white.l);
+        assert!(white.a.abs() < 0.01 && white.b.abs() < 0.01);
+        let black: Lab = Lab::from_srgb(&[0, 0, 0]);
+        assert_close(0f64, black.l);
+    }
+    #[test]
+    fn delta_e_76_euclidean() {
+        let first: Lab = Lab::new(50f64, 0f64, 0f64);
+        let second: Lab = Lab::new(53f64, 4f64, 0f64);
+        assert_close(5f64, delta_e_76(&first, &second));
+    }
+    #[test]
+    fn delta_e_94_same_color() {
+        let first: Lab = Lab::new(50f64, 2.6772, -79.7751);
+        assert_close(0f64, delta_e_94(&first, &first));
+    }
+    // pairs of the CIEDE2000 reference data below, with the graphic arts weighting
+    #[test]
+    fn delta_e_94_reference_values() {
+        assert_close(
+            1.3950,
+            delta_e_94(
+                &Lab::new(50f64, 2.6772, -79.7751),
+                &Lab::new(50f64, 0f64, -82.7485),
+            ),
+        );
+        assert_close(
+            34.6892,
+            delta_e_94(&Lab::new(50f64, 2.5, 0f64), &Lab::new(73f64, 25f64, -18f64)),
+        );
+        assert_close(
+            1.3065,
+            delta_e_94(
+                &Lab::new(2.0776, 0.0795, -1.135),
+                &Lab::new(0.9033, -0.0636, -0.5514),
+            ),
+        );
+    }
+    // the chroma weighting uses the first color as the reference
+    #[test]
+    fn delta_e_94_is_not_symmetric() {
+        assert_close(
+            1.3653,
+            delta_e_94(
+                &Lab::new(50f64, 0f64, -82.7485),
+                &Lab::new(50f64, 2.6772, -79.7751),
+            ),
+        );
+    }
+    // reference values from Sharma, Wu & Dalal, "The CIEDE2000 color-difference formula"
+    #[test]
+    fn ciede2000_reference_values() {
+        assert_close(
+            2.0425,
+            ciede2000(
+                &Lab::new(50f64, 2.6772, -79.7751),
+                &Lab::new(50f64, 0f64, -82.7485),
+            ),
+        );
+        assert_close(
+            27.1492,
+            ciede2000(&Lab::new(50f64, 2.5, 0f64), &Lab::new(73f64, 25f64, -18f64)),
+        );
+        assert_close(
+            0.9082,
+            ciede2000(
+                &Lab::new(2.0776, 0.0795, -1.135),
+                &Lab::new(0.9033, -0.0636, -0.5514),
+            ),
+        );
+    }
+}
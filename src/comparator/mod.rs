@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::core::{compare_decoded_images, validate_image_format};
+use crate::model::{
+    ComparisonBuffers, ImageComparisonOptions, ImageComparisonResult, ImageComparisonState,
+};
+use image::DynamicImage;
+
+/// Compares images with the same options, for comparing many captures against a few
+/// expected images.
+///
+/// Decoded expected images are cached by path and modification time, so an expected image
+/// is decoded again only when its file changes. The cache holds up to `cache_capacity`
+/// images and drops the least recently used one when it is full. The internal buffers of a
+/// comparison that does not mismatch are reused by the next comparison of images with the
+/// same dimensions; a mismatching result keeps them for rendering.
+pub struct Comparator {
+    options: ImageComparisonOptions,
+    cache_capacity: usize,
+    expected_images: HashMap<PathBuf, CachedImage>,
+    // incremented on every cache access to find the least recently used image
+    cache_clock: u64,
+    buffers: ComparisonBuffers,
+}
+
+struct CachedImage {
+    modified: SystemTime,
+    image: Arc<DynamicImage>,
+    last_used: u64,
+}
+
+impl Comparator {
+    /// Creates a comparator whose cache holds up to 16 expected images.
+    pub fn new(options: ImageComparisonOptions) -> Self {
+        Self::with_cache_capacity(options, 16)
+    }
+    /// Creates a comparator whose cache holds up to `cache_capacity` expected images, 0
+    /// disables the cache.
+    pub fn with_cache_capacity(options: ImageComparisonOptions, cache_capacity: usize) -> Self {
+        Self {
+            options,
+            cache_capacity,
+            expected_images: HashMap::new(),
+            cache_clock: 0,
+            buffers: ComparisonBuffers::default(),
+        }
+    }
+    pub fn options(&self) -> &ImageComparisonOptions {
+        &self.options
+    }
+    /// Number of decoded expected images in the cache.
+    pub fn cached_image_count(&self) -> usize {
+        self.expected_images.len()
+    }
+    pub fn clear_cache(&mut self) {
+        self.expected_images.clear();
+    }
+    /// Compares the images like `compare_images_with_options` with the options of the
+    /// comparator. Images that can not be decoded give
+    /// `ImageComparisonState::FormatNotSupported`.
+    pub fn compare(
+        &mut self,
+        expected_image: &OsStr,
+        actual_image: &OsStr,
+    ) -> ImageComparisonResult {
+        if let Some(image_comparison_result) = validate_image_format(expected_image, actual_image) {
+            return image_comparison_result;
+        }
+        let expected: Arc<DynamicImage> = match self.load_expected_image(Path::new(expected_image))
+        {
+            Ok(expected) => expected,
+            Err(image_comparison_state) => {
+                return ImageComparisonResult::create_with_state(image_comparison_state)
+            }
+        };
+        let actual: DynamicImage = match open_image(Path::new(actual_image)) {
+            Ok(actual) => actual,
+            Err(image_comparison_state) => {
+                return ImageComparisonResult::create_with_state(image_comparison_state)
+            }
+        };
+        compare_decoded_images(expected, actual, &self.options, &mut self.buffers)
+    }
+    // files without a modification time are decoded every time
+    fn load_expected_image(
+        &mut self,
+        path: &Path,
+    ) -> Result<Arc<DynamicImage>, ImageComparisonState> {
+        let modified: Option<SystemTime> = fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        let modified: SystemTime = match modified {
+            Some(modified) if self.cache_capacity > 0 => modified,
+            _ => return open_image(path).map(Arc::new),
+        };
+        self.cache_clock += 1;
+        if let Some(cached_image) = self.expected_images.get_mut(path) {
+            if cached_image.modified == modified {
+                cached_image.last_used = self.cache_clock;
+                return Ok(Arc::clone(&cached_image.image));
+            }
+        }
+        let expected: Arc<DynamicImage> = Arc::new(open_image(path)?);
+        if !self.expected_images.contains_key(path)
+            && self.expected_images.len() >= self.cache_capacity
+        {
+            self.evict_least_recently_used();
+        }
+        self.expected_images.insert(
+            path.to_path_buf(),
+            CachedImage {
+                modified,
+                image: Arc::clone(&expected),
+                last_used: self.cache_clock,
+            },
+        );
+        Ok(expected)
+    }
+    fn evict_least_recently_used(&mut self) {
+        let least_recently_used: Option<PathBuf> = self
+            .expected_images
+            .iter()
+            .min_by_key(|(_, cached_image)| cached_image.last_used)
+            .map(|(path, _)| path.clone());
+        if let Some(path) = least_recently_used {
+            self.expected_images.remove(&path);
+        }
+    }
+}
+
+fn open_image(path: &Path) -> Result<DynamicImage, ImageComparisonState> {
+    image::open(path).map_err(|error| {
+        eprintln!("image: {:?} can not be decoded: {}", path, error);
+        ImageComparisonState::FormatNotSupported
+    })
+}
@@ -0,0 +1,123 @@
+use crate::model::Point;
+
+// clockwise in image coordinates (y grows downwards)
+const EAST: usize = 0;
+const SOUTH: usize = 1;
+const WEST: usize = 2;
+const NORTH: usize = 3;
+const DIRECTIONS: [(isize, isize); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+
+// Traces the outer boundary of the 8-connected component containing start along the pixel
+// edges and returns the polygon vertices in pixel corner coordinates, clockwise.
+// start must be the first pixel of the component in raster order.
+pub(crate) fn trace_contour<F>(start: Point, is_foreground: F) -> Vec<Point>
+where
+    F: Fn(isize, isize) -> bool,
+{
+    let start_corner: (isize, isize) = (start.x as isize, start.y as isize);
+    let mut corner: (isize, isize) = start_corner;
+    // the left edge of the start pixel is the last edge of the contour
+    let mut direction: usize = NORTH;
+    let mut vertices: Vec<Point> = Vec::new();
+    loop {
+        let next_direction: usize = next_direction(&corner, direction, &is_foreground);
+        if next_direction != direction {
+            vertices.push(Point::new(corner.0 as usize, corner.1 as usize));
+        }
+        direction = next_direction;
+        corner = (
+            corner.0 + DIRECTIONS[direction].0,
+            corner.1 + DIRECTIONS[direction].1,
+        );
+        if corner == start_corner {
+            break;
+        }
+    }
+    vertices
+}
+
+// keeps the foreground on the right-hand side, preferring left turns so that diagonally
+// touching pixels belong to the same contour
+fn next_direction<F>(corner: &(isize, isize), direction: usize, is_foreground: &F) -> usize
+where
+    F: Fn(isize, isize) -> bool,
+{
+    let (x, y) = *corner;
+    let (left_ahead, right_ahead) = match direction {
+        EAST => ((x, y - 1), (x, y)),
+        SOUTH => ((x, y), (x - 1, y)),
+        WEST => ((x - 1, y), (x - 1, y - 1)),
+        _ => ((x - 1, y - 1), (x, y - 1)),
+    };
+    if is_foreground(left_ahead.0, left_ahead.1) {
+        (direction + 3) % 4
+    } else if is_foreground(right_ahead.0, right_ahead.1) {
+        direction
+    } else {
+        (direction + 1) % 4
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::trace_contour;
+    use crate::model::Point;
+    use ndarray::{arr2, Array2};
+    fn trace(mask: &Array2<u8>) -> Vec<(usize, usize)> {
+        let start: Point = mask
+            .indexed_iter()
+            .find(|(_, value)| **value == 1)
+            .map(|((y, x), _)| Point::new(x, y))
+            .unwrap();
+        trace_contour(start, |x, y| {
+            x >= 0
+                && y >= 0
+                && (y as usize) < mask.nrows()
+                && (x as usize) < mask.ncols()
+                && mask[[y as usize, x as usize]] == 1
+        })
+        .iter()
+        .map(|point| (point.x, point.y))
+        .collect()
+    }
+    #[test]
+    fn trace_contour_single_pixel() {
+        let mask: Array2<u8> = arr2(&[[0, 0, 0], [0, 1, 0], [0, 0, 0]]);
+        assert_eq!(vec![(1, 1), (2, 1), (2, 2), (1, 2)], trace(&mask));
+    }
+    #[test]
+    fn trace_contour_square() {
+        let mask: Array2<u8> = arr2(&[[1, 1, 1], [1, 1, 1], [1, 1, 1]]);
+        assert_eq!(vec![(0, 0), (3, 0), (3, 3), (0, 3)], trace(&mask));
+    }
+    #[test]
+    fn trace_contour_l_shape() {
+        let mask: Array2<u8> = arr2(&[[1, 0, 0, 0], [1, 0, 0, 0], [1, 0, 0, 0], [1, 1, 1, 1]]);
+        assert_eq!(
+            vec![(0, 0), (1, 0), (1, 3), (4, 3), (4, 4), (0, 4)],
+            trace(&mask)
+        );
+    }
+    #[test]
+    fn trace_contour_diagonal_pixels() {
+        let mask: Array2<u8> = arr2(&[[1, 0], [0, 1]]);
+        assert_eq!(
+            vec![
+                (0, 0),
+                (1, 0),
+                (1, 1),
+                (2, 1),
+                (2, 2),
+                (1, 2),
+                (1, 1),
+                (0, 1)
+            ],
+            trace(&mask)
+        );
+    }
+    #[test]
+    fn trace_contour_ignores_holes() {
+        let mask: Array2<u8> = arr2(&[[1, 1, 1], [1, 0, 1], [1, 1, 1]]);
+        assert_eq!(vec![(0, 0), (3, 0), (3, 3), (0, 3)], trace(&mask));
+    }
+}
@@ -1,52 +1,316 @@
 use std::ffi::OsStr;
 
-use crate::model::{ImageComparison, ImageComparisonResult, ImageComparisonState, Rectangle};
+use crate::bitmatrix::BitMatrix;
+use crate::color;
+use crate::contour;
+use crate::grouping::{DifferenceMask, PixelRegion, PixelRun, RegionGrouping, ThresholdGrouping};
+use crate::model::{
+    ColorDifference, ComparisonBuffers, DeltaEStatistics, DifferenceGrid, GridCell,
+    ImageComparison, ImageComparisonOptions, ImageComparisonResult, ImageComparisonState, Point,
+    Rectangle, Region, RenderData, Severity, SizeMismatchPolicy,
+};
+use crate::morphology;
+use crate::pixel::PixelComparison;
+use crate::tiled;
+use image::imageops::{self, FilterType};
 use image::{ColorType, DynamicImage, GenericImageView, ImageFormat, Rgba, RgbaImage};
+use ndarray::Array2;
+use png::Reader;
+use std::cmp::{max, min};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::sync::Arc;
 
 pub fn compare_images(expected_image: &OsStr, actual_image: &OsStr) -> ImageComparisonResult {
+    compare_images_with_options(
+        expected_image,
+        actual_image,
+        &ImageComparisonOptions::default(),
+    )
+}
+
+pub fn compare_images_with_options(
+    expected_image: &OsStr,
+    actual_image: &OsStr,
+    options: &ImageComparisonOptions,
+) -> ImageComparisonResult {
     if let Some(image_comparison_result) = validate_image_format(expected_image, actual_image) {
         return image_comparison_result;
     }
     let expected: DynamicImage = image::open(expected_image).unwrap();
     let actual: DynamicImage = image::open(actual_image).unwrap();
+    compare_decoded_images(
+        Arc::new(expected),
+        actual,
+        options,
+        &mut ComparisonBuffers::default(),
+    )
+}
+
+// the expected image is shared, e.g. with a cache, and copied only when it is padded,
+// resampled or shifted; buffers of a previous comparison with the same dimensions are reused,
+// they are returned on every outcome except a mismatch, whose result keeps them for rendering
+pub(crate) fn compare_decoded_images(
+    expected: Arc<DynamicImage>,
+    actual: DynamicImage,
+    options: &ImageComparisonOptions,
+    buffers: &mut ComparisonBuffers,
+) -> ImageComparisonResult {
     if let Some(image_comparison_result) = validate_color_type(&expected, &actual) {
         return image_comparison_result;
     }
-    if let Some(image_comparison_result) = validate_dimensions(&expected, &actual) {
-        return image_comparison_result;
+    let expected_dimensions: (u32, u32) = expected.dimensions();
+    let actual_dimensions: (u32, u32) = actual.dimensions();
+    let (expected, actual, size_mismatch_areas) =
+        match apply_size_mismatch_policy(expected, actual, options.size_mismatch_policy) {
+            Ok(images) => images,
+            Err(image_comparison_state) => {
+                let mut image_comparison_result: ImageComparisonResult =
+                    ImageComparisonResult::create_with_state(image_comparison_state);
+                image_comparison_result.expected_dimensions = expected_dimensions;
+                image_comparison_result.actual_dimensions = actual_dimensions;
+                image_comparison_result.is_size_mismatch = true;
+                return image_comparison_result;
+            }
+        };
+    let is_resampled: bool = matches!(
+        options.size_mismatch_policy,
+        SizeMismatchPolicy::Resample(_)
+    ) && !dimensions_are_equal(&expected_dimensions, &actual_dimensions);
+    let pixel_tolerance: u8 = if is_resampled {
+        max(options.color_tolerance, options.resampling_tolerance)
+    } else {
+        options.color_tolerance
+    };
+    let pixel_comparison: PixelComparison =
+        PixelComparison::create_with_options(options, pixel_tolerance);
+    let mut detected_offset: (i32, i32) = (0, 0);
+    let mut exposed_areas: Vec<Rectangle> = Vec::new();
+    let expected: Arc<DynamicImage> = if options.shift_search_radius > 0 {
+        detected_offset = find_best_offset(&expected, &actual, options, &pixel_comparison);
+        exposed_areas = find_exposed_areas(&expected.dimensions(), &detected_offset);
+        Arc::new(shift_image(&expected, &detected_offset))
+    } else {
+        expected
+    };
+    let mut image_comparison: ImageComparison =
+        ImageComparison::create_with_options(expected, actual, options, pixel_comparison, buffers);
+    image_comparison.size_mismatch_areas = size_mismatch_areas;
+    image_comparison.exposed_areas = exposed_areas;
+    if let Some(mask_image) = &options.mask_image {
+        match load_mask(mask_image.as_os_str(), &image_comparison) {
+            Ok(mask) => image_comparison.mask = Some(mask),
+            Err(image_comparison_state) => {
+                buffers.difference_mask = Some(image_comparison.difference_mask);
+                return ImageComparisonResult::create_with_state(image_comparison_state);
+            }
+        }
+    }
+    if let Some(ignore_color) = options.ignore_color {
+        image_comparison.color_key = Some(find_color_key(
+            &image_comparison,
+            ignore_color,
+            options.ignore_color_tolerance,
+        ));
+    }
+    let mut pixel_regions: Vec<PixelRegion> = populate_pixel_regions(&mut image_comparison);
+    if let Some(grid_size) = options.grid_size {
+        image_comparison.grid = Some(create_difference_grid(
+            &image_comparison,
+            grid_size,
+            options.grid_cell_threshold,
+        ));
     }
-    let mut image_comparison: ImageComparison = ImageComparison::new(expected, actual);
-    let rectangles: Vec<Rectangle> = populate_rectangles(&mut image_comparison);
-    println!("rectangles.len: {}", rectangles.len());
-    if rectangles.len() > 0 {
-        let result_image: RgbaImage =
-            draw_rectangles(&image_comparison, rectangles, Rgba::<u8>([255, 0, 0, 255]));
-        return ImageComparisonResult {
-            image_comparison_state: ImageComparisonState::Mismatch,
-            result_image: Some(result_image),
+    if !image_comparison.size_mismatch_areas.is_empty() {
+        pixel_regions.extend(
+            image_comparison
+                .size_mismatch_areas
+                .iter()
+                .map(|size_mismatch_area| PixelRegion::create_with_rectangle(*size_mismatch_area)),
+        );
+        if options.merge_rectangles {
+            pixel_regions = merge_pixel_regions(pixel_regions, options.merge_distance as usize);
+        }
+    }
+    let mut image_comparison_result: ImageComparisonResult =
+        ImageComparisonResult::create_with_state(ImageComparisonState::Match);
+    image_comparison_result.difference_percent = image_comparison.difference_percent;
+    image_comparison_result.expected_dimensions = expected_dimensions;
+    image_comparison_result.actual_dimensions = actual_dimensions;
+    image_comparison_result.is_size_mismatch =
+        !dimensions_are_equal(&expected_dimensions, &actual_dimensions);
+    // decided before max_regions drops regions, which can drop all of them
+    let is_mismatch: bool = !pixel_regions.is_empty();
+    let mut regions: Vec<Region> = create_regions(&image_comparison, &pixel_regions);
+    if let Some(max_regions) = options.max_regions {
+        if regions.len() > max_regions {
+            let truncated_regions: Vec<Region> = regions.split_off(max_regions);
+            image_comparison_result.is_truncated = true;
+            image_comparison_result.truncated_region_count = truncated_regions.len();
+            image_comparison.truncated_area = truncated_regions
+                .iter()
+                .map(|region| region.rectangle)
+                .reduce(|first, second| first.merge(&second));
+            image_comparison_result.truncated_area = image_comparison.truncated_area;
+        }
+    }
+    let rectangles: Vec<Rectangle> = regions.iter().map(|region| region.rectangle).collect();
+    image_comparison_result.rectangles = rectangles.clone();
+    image_comparison_result.detected_offset = detected_offset;
+    image_comparison_result.exposed_rectangles = image_comparison.exposed_areas.clone();
+    image_comparison_result.delta_e = image_comparison.delta_e;
+    image_comparison_result.grid = image_comparison.grid.clone();
+    if is_resampled {
+        let lower_resolution: (u32, u32) =
+            lower_resolution_dimensions(&expected_dimensions, &actual_dimensions);
+        let higher_resolution: (u32, u32) = if lower_resolution == expected_dimensions {
+            actual_dimensions
+        } else {
+            expected_dimensions
         };
+        let scale: (f64, f64) = (
+            higher_resolution.0 as f64 / lower_resolution.0 as f64,
+            higher_resolution.1 as f64 / lower_resolution.1 as f64,
+        );
+        image_comparison_result.high_resolution_rectangles = rectangles
+            .iter()
+            .map(|rectangle| scale_rectangle(rectangle, &scale, &higher_resolution))
+            .collect();
     }
-    return ImageComparisonResult {
-        image_comparison_state: ImageComparisonState::Match,
-        result_image: None,
+    if is_mismatch {
+        image_comparison_result.image_comparison_state = ImageComparisonState::Mismatch;
+        image_comparison_result.render_data = Some(RenderData::create_from(image_comparison));
+    } else {
+        buffers.difference_mask = Some(image_comparison.difference_mask);
+    }
+    if image_comparison_result.delta_e.is_some() {
+        image_comparison_result.region_delta_e = regions
+            .iter()
+            .map(|region| DeltaEStatistics {
+                mean: region.mean_color_delta,
+                max: region.max_color_delta,
+            })
+            .collect();
+    }
+    image_comparison_result.regions = regions;
+    image_comparison_result
+}
+
+/// Returns whether the images match without locating the differences, stopping at the first
+/// different pixel.
+///
+/// Byte-equal files are detected by comparing their bytes without decoding them. Otherwise
+/// the pixels are compared row by row with the tolerance and color difference of `options`,
+/// the other options are not used. Images that can not be read row by row, e.g. interlaced,
+/// RGB, palette or 16-bit PNG images, are decoded completely first. Images that can not be
+/// decoded do not match.
+pub fn images_are_identical(
+    expected_image: &OsStr,
+    actual_image: &OsStr,
+    options: &ImageComparisonOptions,
+) -> bool {
+    if files_are_equal(expected_image, actual_image) {
+        return true;
+    }
+    let (mut expected_reader, mut actual_reader): (
+        Reader<BufReader<File>>,
+        Reader<BufReader<File>>,
+    ) = match (
+        tiled::open_reader(expected_image),
+        tiled::open_reader(actual_image),
+    ) {
+        (Ok(expected_reader), Ok(actual_reader)) => (expected_reader, actual_reader),
+        _ => return decoded_images_are_identical(expected_image, actual_image, options),
+    };
+    if expected_reader.info().size() != actual_reader.info().size() {
+        return false;
+    }
+    loop {
+        match (expected_reader.next_row(), actual_reader.next_row()) {
+            (Ok(Some(expected_row)), Ok(Some(actual_row))) => {
+                if !rgba_pixels_match(options, expected_row.data(), actual_row.data()) {
+                    return false;
+                }
+            }
+            (Ok(None), Ok(None)) => return true,
+            _ => return false,
+        }
+    }
+}
+
+fn decoded_images_are_identical(
+    expected_image: &OsStr,
+    actual_image: &OsStr,
+    options: &ImageComparisonOptions,
+) -> bool {
+    let (expected, actual) = match (image::open(expected_image), image::open(actual_image)) {
+        (Ok(expected), Ok(actual)) => (expected.to_rgba8(), actual.to_rgba8()),
+        _ => return false,
+    };
+    expected.dimensions() == actual.dimensions()
+        && rgba_pixels_match(options, expected.as_raw(), actual.as_raw())
+}
+
+fn rgba_pixels_match(options: &ImageComparisonOptions, expected: &[u8], actual: &[u8]) -> bool {
+    let pixel_comparison: PixelComparison =
+        PixelComparison::create_with_options(options, options.color_tolerance);
+    !expected
+        .chunks_exact(4)
+        .zip(actual.chunks_exact(4))
+        .any(|(e, a)| {
+            pixel_comparison.differs(&[e[0], e[1], e[2], e[3]], &[a[0], a[1], a[2], a[3]])
+        })
+}
+
+// both files are streamed and compared chunk by chunk, files of different sizes are not read
+fn files_are_equal(expected_image: &OsStr, actual_image: &OsStr) -> bool {
+    let (expected_file, actual_file) = match (File::open(expected_image), File::open(actual_image))
+    {
+        (Ok(expected_file), Ok(actual_file)) => (expected_file, actual_file),
+        _ => return false,
     };
+    match (expected_file.metadata(), actual_file.metadata()) {
+        (Ok(expected_metadata), Ok(actual_metadata))
+            if expected_metadata.len() == actual_metadata.len() => {}
+        _ => return false,
+    }
+    let mut expected_reader: BufReader<File> = BufReader::new(expected_file);
+    let mut actual_reader: BufReader<File> = BufReader::new(actual_file);
+    loop {
+        let (expected_buffer, actual_buffer) =
+            match (expected_reader.fill_buf(), actual_reader.fill_buf()) {
+                (Ok(expected_buffer), Ok(actual_buffer)) => (expected_buffer, actual_buffer),
+                _ => return false,
+            };
+        let length: usize = min(expected_buffer.len(), actual_buffer.len());
+        if length == 0 {
+            return expected_buffer.len() == actual_buffer.len();
+        }
+        if expected_buffer[..length] != actual_buffer[..length] {
+            return false;
+        }
+        expected_reader.consume(length);
+        actual_reader.consume(length);
+    }
 }
 
-fn validate_image_format(
+pub(crate) fn validate_image_format(
     expected_image: &OsStr,
     actual_image: &OsStr,
 ) -> Option<ImageComparisonResult> {
-    let expected_image_format: ImageFormat = ImageFormat::from_path(expected_image).unwrap();
-    let actual_image_format: ImageFormat = ImageFormat::from_path(actual_image).unwrap();
-    if !(expected_image_format == ImageFormat::Png && actual_image_format == ImageFormat::Png) {
+    // paths without a known image extension have no format
+    let expected_image_format: Option<ImageFormat> = ImageFormat::from_path(expected_image).ok();
+    let actual_image_format: Option<ImageFormat> = ImageFormat::from_path(actual_image).ok();
+    if !(expected_image_format == Some(ImageFormat::Png)
+        && actual_image_format == Some(ImageFormat::Png))
+    {
         eprintln!(
             "image format: {:?} & {:?} are not supported",
             expected_image_format, actual_image_format
         );
-        return Some(ImageComparisonResult {
-            image_comparison_state: ImageComparisonState::FormatNotSupported,
-            result_image: None,
-        });
+        return Some(ImageComparisonResult::create_with_state(
+            ImageComparisonState::FormatNotSupported,
+        ));
     }
     None
 }
@@ -61,10 +325,9 @@ fn validate_color_type(
             expected.color(),
             actual.color()
         );
-        return Some(ImageComparisonResult {
-            image_comparison_state: ImageComparisonState::ColorTypeNotSupported,
-            result_image: None,
-        });
+        return Some(ImageComparisonResult::create_with_state(
+            ImageComparisonState::ColorTypeNotSupported,
+        ));
     }
     None
 }
@@ -79,10 +342,9 @@ fn validate_dimensions(
             expected.dimensions(),
             actual.dimensions()
         );
-        return Some(ImageComparisonResult {
-            image_comparison_state: ImageComparisonState::SizeMismatch,
-            result_image: None,
-        });
+        return Some(ImageComparisonResult::create_with_state(
+            ImageComparisonState::SizeMismatch,
+        ));
     }
     None
 }
@@ -91,256 +353,811 @@ fn dimensions_are_equal(first: &(u32, u32), second: &(u32, u32)) -> bool {
     first.eq(second)
 }
 
-fn populate_rectangles(image_comparison: &mut ImageComparison) -> Vec<Rectangle> {
-    let count_of_different_pixels: usize = populate_matrix(image_comparison);
-    println!("count_of_different_pixels: {}", count_of_different_pixels);
+fn apply_size_mismatch_policy(
+    expected: Arc<DynamicImage>,
+    actual: DynamicImage,
+    size_mismatch_policy: SizeMismatchPolicy,
+) -> Result<(Arc<DynamicImage>, DynamicImage, Vec<Rectangle>), ImageComparisonState> {
+    if dimensions_are_equal(&expected.dimensions(), &actual.dimensions()) {
+        return Ok((expected, actual, Vec::new()));
+    }
+    match size_mismatch_policy {
+        SizeMismatchPolicy::Reject => match validate_dimensions(&expected, &actual) {
+            Some(image_comparison_result) => Err(image_comparison_result.image_comparison_state),
+            None => Ok((expected, actual, Vec::new())),
+        },
+        SizeMismatchPolicy::CompareOverlap => {
+            let size_mismatch_areas: Vec<Rectangle> =
+                find_size_mismatch_areas(&expected.dimensions(), &actual.dimensions());
+            let (width, height) = union_dimensions(&expected.dimensions(), &actual.dimensions());
+            let transparent: Rgba<u8> = Rgba::<u8>([0, 0, 0, 0]);
+            Ok((
+                Arc::new(pad_image(&expected, width, height, transparent)),
+                pad_image(&actual, width, height, transparent),
+                size_mismatch_areas,
+            ))
+        }
+        SizeMismatchPolicy::Pad(color) => {
+            let size_mismatch_areas: Vec<Rectangle> =
+                find_size_mismatch_areas(&expected.dimensions(), &actual.dimensions());
+            let (width, height) = union_dimensions(&expected.dimensions(), &actual.dimensions());
+            Ok((
+                Arc::new(pad_image(&expected, width, height, color)),
+                pad_image(&actual, width, height, color),
+                size_mismatch_areas,
+            ))
+        }
+        SizeMismatchPolicy::Scale => {
+            let (width, height) = expected.dimensions();
+            let actual: DynamicImage = actual.resize_exact(width, height, FilterType::Triangle);
+            Ok((expected, actual, Vec::new()))
+        }
+        SizeMismatchPolicy::Resample(filter) => {
+            let (width, height) =
+                lower_resolution_dimensions(&expected.dimensions(), &actual.dimensions());
+            let expected: Arc<DynamicImage> =
+                if dimensions_are_equal(&expected.dimensions(), &(width, height)) {
+                    expected
+                } else {
+                    Arc::new(resample_image(&expected, width, height, filter))
+                };
+            let actual: DynamicImage =
+                if dimensions_are_equal(&actual.dimensions(), &(width, height)) {
+                    actual
+                } else {
+                    resample_image(&actual, width, height, filter)
+                };
+            Ok((expected, actual, Vec::new()))
+        }
+    }
+}
+
+fn lower_resolution_dimensions(first: &(u32, u32), second: &(u32, u32)) -> (u32, u32) {
+    if (first.0 as u64 * first.1 as u64) <= (second.0 as u64 * second.1 as u64) {
+        *first
+    } else {
+        *second
+    }
+}
+
+fn resample_image(
+    image: &DynamicImage,
+    width: u32,
+    height: u32,
+    filter: FilterType,
+) -> DynamicImage {
+    DynamicImage::ImageRgba8(imageops::resize(image, width, height, filter))
+}
+
+fn scale_rectangle(
+    rectangle: &Rectangle,
+    scale: &(f64, f64),
+    dimensions: &(u32, u32),
+) -> Rectangle {
+    let min_x: usize = (rectangle.min_point.x as f64 * scale.0).floor() as usize;
+    let min_y: usize = (rectangle.min_point.y as f64 * scale.1).floor() as usize;
+    let max_x: usize = ((rectangle.max_point.x + 1) as f64 * scale.0).ceil() as usize - 1;
+    let max_y: usize = ((rectangle.max_point.y + 1) as f64 * scale.1).ceil() as usize - 1;
+    Rectangle::create_with_coordinates(
+        min_x,
+        min_y,
+        min(max_x, dimensions.0 as usize - 1),
+        min(max_y, dimensions.1 as usize - 1),
+    )
+}
+
+fn union_dimensions(first: &(u32, u32), second: &(u32, u32)) -> (u32, u32) {
+    (max(first.0, second.0), max(first.1, second.1))
+}
+
+fn find_size_mismatch_areas(first: &(u32, u32), second: &(u32, u32)) -> Vec<Rectangle> {
+    let (width, height) = union_dimensions(first, second);
+    let overlap_width: u32 = min(first.0, second.0);
+    let overlap_height: u32 = min(first.1, second.1);
+    let mut size_mismatch_areas: Vec<Rectangle> = Vec::new();
+    if overlap_width < width {
+        size_mismatch_areas.push(Rectangle::create_with_coordinates(
+            overlap_width as usize,
+            0,
+            width as usize - 1,
+            height as usize - 1,
+        ));
+    }
+    if overlap_height < height && overlap_width > 0 {
+        size_mismatch_areas.push(Rectangle::create_with_coordinates(
+            0,
+            overlap_height as usize,
+            overlap_width as usize - 1,
+            height as usize - 1,
+        ));
+    }
+    size_mismatch_areas
+}
+
+fn pad_image(image: &DynamicImage, width: u32, height: u32, color: Rgba<u8>) -> DynamicImage {
+    let mut result: RgbaImage = RgbaImage::from_pixel(width, height, color);
+    imageops::replace(&mut result, &image.to_rgba8(), 0, 0);
+    DynamicImage::ImageRgba8(result)
+}
+
+fn load_mask(
+    mask_image: &OsStr,
+    image_comparison: &ImageComparison,
+) -> Result<BitMatrix, ImageComparisonState> {
+    let mask: DynamicImage = match image::open(mask_image) {
+        Ok(mask) => mask,
+        Err(error) => {
+            eprintln!(
+                "mask image: {:?} could not be opened: {}",
+                mask_image, error
+            );
+            return Err(ImageComparisonState::FormatNotSupported);
+        }
+    };
+    let image_dimensions: (u32, u32) =
+        (image_comparison.image_width, image_comparison.image_height);
+    if !dimensions_are_equal(&image_dimensions, &mask.dimensions()) {
+        eprintln!(
+            "mask image dimensions: {:?} and image dimensions: {:?} are not equal",
+            mask.dimensions(),
+            image_dimensions
+        );
+        return Err(ImageComparisonState::SizeMismatch);
+    }
+    // masks with transparent pixels ignore the non-transparent pixels, all other masks,
+    // including opaque black and white masks with an alpha channel, ignore nonzero pixels
+    let mask_imgbuf: RgbaImage = mask.to_rgba8();
+    let has_alpha: bool = mask.color().has_alpha()
+        && mask_imgbuf
+            .pixels()
+            .any(|mask_pixel| mask_pixel.0[3] != 255);
+    let mut result: BitMatrix = BitMatrix::new(
+        image_comparison.image_width as usize,
+        image_comparison.image_height as usize,
+    );
+    for (x, y, mask_pixel) in mask_imgbuf.enumerate_pixels() {
+        let m: [u8; 4] = mask_pixel.0;
+        let is_masked: bool = if has_alpha {
+            m[3] != 0
+        } else {
+            m[0] != 0 || m[1] != 0 || m[2] != 0
+        };
+        result.set(x as usize, y as usize, is_masked);
+    }
+    Ok(result)
+}
+
+fn find_color_key(
+    image_comparison: &ImageComparison,
+    ignore_color: Rgba<u8>,
+    tolerance: u8,
+) -> BitMatrix {
+    let e_imgbuf: &RgbaImage = image_comparison.expected.as_rgba8().expect("msg");
+    let mut result: BitMatrix = BitMatrix::new(
+        image_comparison.image_width as usize,
+        image_comparison.image_height as usize,
+    );
+    for (x, y, expected_pixel) in e_imgbuf.enumerate_pixels() {
+        let is_color_keyed: bool = expected_pixel
+            .0
+            .iter()
+            .zip(ignore_color.0.iter())
+            .all(|(e, k)| e.abs_diff(*k) <= tolerance);
+        result.set(x as usize, y as usize, is_color_keyed);
+    }
+    result
+}
+
+// offsets beyond the image dimensions leave no overlap, so the radius is clamped to them
+fn find_best_offset(
+    expected: &DynamicImage,
+    actual: &DynamicImage,
+    options: &ImageComparisonOptions,
+    pixel_comparison: &PixelComparison,
+) -> (i32, i32) {
+    let e_imgbuf: &RgbaImage = expected.as_rgba8().expect("msg");
+    let a_imgbuf: &RgbaImage = actual.as_rgba8().expect("msg");
+    let (width, height) = a_imgbuf.dimensions();
+    let radius: i32 =
+        min(options.shift_search_radius, max(width, height)).min(i32::MAX as u32) as i32;
+    let different_pixels_ratio = |offset: &(i32, i32)| -> f64 {
+        different_pixels_ratio(e_imgbuf, a_imgbuf, offset, |e, a| {
+            pixel_comparison.differs(e, a)
+        })
+    };
+    let mut best_offset: (i32, i32) = (0, 0);
+    let mut best_ratio: f64 = different_pixels_ratio(&best_offset);
+    for dy in -radius..=radius {
+        for dx in -radius..=radius {
+            if best_ratio == 0f64 {
+                return best_offset;
+            }
+            let offset: (i32, i32) = (dx, dy);
+            let ratio: f64 = different_pixels_ratio(&offset);
+            if ratio < best_ratio
+                || (ratio == best_ratio
+                    && dx.abs() + dy.abs() < best_offset.0.abs() + best_offset.1.abs())
+            {
+                best_ratio = ratio;
+                best_offset = offset;
+            }
+        }
+    }
+    best_offset
+}
+
+// ratio of different pixels between expected (x - dx, y - dy) and actual (x, y) in their overlap
+fn different_pixels_ratio<F>(
+    e_imgbuf: &RgbaImage,
+    a_imgbuf: &RgbaImage,
+    offset: &(i32, i32),
+    pixels_differ: F,
+) -> f64
+where
+    F: Fn(&[u8; 4], &[u8; 4]) -> bool,
+{
+    let (width, height) = a_imgbuf.dimensions();
+    let (dx, dy) = *offset;
+    let min_x: u32 = max(dx, 0) as u32;
+    let min_y: u32 = max(dy, 0) as u32;
+    let max_x: i64 = width as i64 + min(dx, 0) as i64;
+    let max_y: i64 = height as i64 + min(dy, 0) as i64;
+    if max_x <= min_x as i64 || max_y <= min_y as i64 {
+        return f64::MAX;
+    }
+    let mut count_of_different_pixels: usize = 0;
+    for y in min_y..max_y as u32 {
+        for x in min_x..max_x as u32 {
+            let expected_pixel: &Rgba<u8> =
+                e_imgbuf.get_pixel((x as i32 - dx) as u32, (y as i32 - dy) as u32);
+            if pixels_differ(&expected_pixel.0, &a_imgbuf.get_pixel(x, y).0) {
+                count_of_different_pixels += 1;
+            }
+        }
+    }
+    let overlap_pixel_count: f64 = (max_x - min_x as i64) as f64 * (max_y - min_y as i64) as f64;
+    count_of_different_pixels as f64 / overlap_pixel_count
+}
+
+fn find_exposed_areas(dimensions: &(u32, u32), offset: &(i32, i32)) -> Vec<Rectangle> {
+    let width: usize = dimensions.0 as usize;
+    let height: usize = dimensions.1 as usize;
+    let shift_x: usize = min(offset.0.unsigned_abs() as usize, width);
+    let shift_y: usize = min(offset.1.unsigned_abs() as usize, height);
+    let mut exposed_areas: Vec<Rectangle> = Vec::new();
+    if shift_x > 0 {
+        let (min_x, max_x) = if offset.0 > 0 {
+            (0, shift_x - 1)
+        } else {
+            (width - shift_x, width - 1)
+        };
+        exposed_areas.push(Rectangle::create_with_coordinates(
+            min_x,
+            0,
+            max_x,
+            height - 1,
+        ));
+    }
+    if shift_y > 0 && shift_x < width {
+        let (min_x, max_x) = if offset.0 > 0 {
+            (shift_x, width - 1)
+        } else {
+            (0, width - shift_x - 1)
+        };
+        let (min_y, max_y) = if offset.1 > 0 {
+            (0, shift_y - 1)
+        } else {
+            (height - shift_y, height - 1)
+        };
+        exposed_areas.push(Rectangle::create_with_coordinates(
+            min_x, min_y, max_x, max_y,
+        ));
+    }
+    exposed_areas
+}
+
+fn shift_image(image: &DynamicImage, offset: &(i32, i32)) -> DynamicImage {
+    let imgbuf: &RgbaImage = image.as_rgba8().expect("msg");
+    let (width, height) = imgbuf.dimensions();
+    let mut result: RgbaImage = RgbaImage::from_pixel(width, height, Rgba::<u8>([0, 0, 0, 0]));
+    for (x, y, pixel) in imgbuf.enumerate_pixels() {
+        let shifted_x: i64 = x as i64 + offset.0 as i64;
+        let shifted_y: i64 = y as i64 + offset.1 as i64;
+        if shifted_x >= 0 && shifted_x < width as i64 && shifted_y >= 0 && shifted_y < height as i64
+        {
+            result.put_pixel(shifted_x as u32, shifted_y as u32, *pixel);
+        }
+    }
+    DynamicImage::ImageRgba8(result)
+}
+
+fn populate_pixel_regions(image_comparison: &mut ImageComparison) -> Vec<PixelRegion> {
+    let mut count_of_different_pixels: usize = populate_difference_mask(image_comparison);
+    if !image_comparison.morphological_operations.is_empty() {
+        count_of_different_pixels = apply_morphological_operations(image_comparison);
+    }
+    image_comparison.difference_percent =
+        percent_of_different_pixels(image_comparison, &count_of_different_pixels);
     if count_of_different_pixels == 0usize
-        || is_allowed_percent_of_different_pixels(image_comparison, &count_of_different_pixels)
+        || image_comparison.difference_percent
+            <= image_comparison.allowing_percent_of_different_pixels
     {
-        return Vec::<Rectangle>::new();
-    }
-    group_regions(image_comparison);
-    let mut rectangles: Vec<Rectangle> = Vec::new();
-    let default_rectangle: Rectangle = Rectangle::create_default();
-    while image_comparison.counter <= image_comparison.region_count {
-        // println!(
-        //     "{} {}",
-        //     image_comparison.counter, image_comparison.region_count
-        // );
-        let rectangle: Rectangle = create_rectangle(image_comparison);
-        // println!(
-        //     "rectangle: {:?} -- {:?}",
-        //     rectangle.min_point, rectangle.max_point
-        // );
-        if !rectangle.equals(&default_rectangle)
-            && rectangle.size() >= image_comparison.minimal_rectangle_size as usize
-        {
-            rectangles.push(rectangle);
+        return Vec::<PixelRegion>::new();
+    }
+    let region_grouping: Arc<dyn RegionGrouping> = match &image_comparison.region_grouping {
+        Some(region_grouping) => Arc::clone(region_grouping),
+        None => Arc::new(ThresholdGrouping {
+            threshold: image_comparison.threshold,
+            merge_distance: if image_comparison.merge_rectangles {
+                Some(image_comparison.merge_distance)
+            } else {
+                None
+            },
+        }),
+    };
+    let grouped_pixel_regions: Vec<PixelRegion> =
+        region_grouping.group(&DifferenceMask::new(&image_comparison.difference_mask));
+    let mut pixel_regions: Vec<PixelRegion> = Vec::new();
+    for pixel_region in grouped_pixel_regions {
+        if is_reported_region(
+            image_comparison,
+            &pixel_region.rectangle,
+            pixel_region.pixel_count(),
+        ) {
+            pixel_regions.push(pixel_region);
+        } else {
+            clear_region(image_comparison, &pixel_region);
         }
-        image_comparison.counter += 1;
     }
-    //println!("rectangles.len before merge: {}", rectangles.len());
-    merge_rectangles(merge_rectangles(rectangles))
+    match region_grouping.merge_distance() {
+        Some(merge_distance) => merge_pixel_regions(pixel_regions, merge_distance as usize),
+        None => pixel_regions,
+    }
 }
 
-fn populate_matrix(image_comparison: &mut ImageComparison) -> usize {
+// dilation can spread into ignored pixels, so they are cleared again before counting
+fn apply_morphological_operations(image_comparison: &mut ImageComparison) -> usize {
+    let kernel_size: usize = image_comparison.morphology_kernel_size as usize;
+    for operation in image_comparison.morphological_operations.clone() {
+        morphology::apply(
+            &mut image_comparison.difference_mask,
+            operation,
+            kernel_size,
+        );
+    }
+    let ignored_pixels: Vec<(usize, usize)> = image_comparison
+        .difference_mask
+        .iter_ones()
+        .filter(|(x, y)| image_comparison.is_ignored(*x, *y))
+        .collect();
+    for (x, y) in ignored_pixels {
+        image_comparison.difference_mask.set(x, y, false);
+    }
+    image_comparison.difference_mask.count_ones()
+}
+
+fn populate_difference_mask(image_comparison: &mut ImageComparison) -> usize {
     let mut count_of_different_pixels: usize = 0;
     let e_imgbuf: &RgbaImage = image_comparison.expected.as_rgba8().expect("msg");
     let a_imgbuf: &RgbaImage = image_comparison.actual.as_rgba8().expect("msg");
+    let is_delta_e: bool = image_comparison.color_difference != ColorDifference::Rgb;
+    let mut sum_delta_e: f64 = 0f64;
+    let mut max_delta_e: f64 = 0f64;
+    let mut compared_pixel_count: usize = 0;
     for (x, y, expected_pixel) in e_imgbuf.enumerate_pixels() {
+        if image_comparison.is_ignored(x as usize, y as usize) {
+            continue;
+        }
         let actual_pixel: &Rgba<u8> = a_imgbuf.get_pixel(x, y);
         let e: [u8; 4] = expected_pixel.0;
         let a: [u8; 4] = actual_pixel.0;
-        if e.ne(&a) {
+        // the ΔE of the statistics also decides whether the pixels differ
+        let pixel_delta_e: Option<f64> = if is_delta_e {
+            let pixel_delta_e: f64 = color::delta_e(image_comparison.color_difference, &e, &a);
+            sum_delta_e += pixel_delta_e;
+            max_delta_e = max_delta_e.max(pixel_delta_e);
+            compared_pixel_count += 1;
+            Some(pixel_delta_e)
+        } else {
+            None
+        };
+        let is_pixel_different: bool =
+            image_comparison
+                .pixel_comparison
+                .differs_with_delta_e(&e, &a, pixel_delta_e);
+        if is_pixel_different
+            && !matches_in_neighborhood(image_comparison, e_imgbuf, a_imgbuf, x, y)
+        {
             count_of_different_pixels += 1;
             //println!("x: {} -- y: {}", x, y);
-            image_comparison.matrix[[y as usize, x as usize]] = 1;
+            image_comparison
+                .difference_mask
+                .set(x as usize, y as usize, true);
         }
     }
+    if is_delta_e {
+        let mean: f64 = if compared_pixel_count == 0 {
+            0f64
+        } else {
+            sum_delta_e / compared_pixel_count as f64
+        };
+        image_comparison.delta_e = Some(DeltaEStatistics {
+            mean,
+            max: max_delta_e,
+        });
+    }
     count_of_different_pixels
 }
 
-fn is_allowed_percent_of_different_pixels(
+fn matches_in_neighborhood(
     image_comparison: &ImageComparison,
-    count_of_different_pixels: &usize,
+    e_imgbuf: &RgbaImage,
+    a_imgbuf: &RgbaImage,
+    x: u32,
+    y: u32,
 ) -> bool {
-    let total_pixel_count: usize =
-        image_comparison.matrix.nrows() * image_comparison.matrix.ncols();
-    let actual_percent_of_different_pixels: f64 =
-        (*count_of_different_pixels as f64 / total_pixel_count as f64) * 100f64;
-    actual_percent_of_different_pixels <= image_comparison.allowing_percent_of_different_pixels
-}
-
-fn group_regions(image_comparison: &mut ImageComparison) {
-    for y in 0..image_comparison.matrix.nrows() {
-        for x in 0..image_comparison.matrix.ncols() {
-            if image_comparison.matrix[[y, x]] == 1 {
-                //println!("x: {} -- y: {}", x, y);
-                join_to_region(image_comparison, x, y);
-                image_comparison.region_count += 1;
+    if image_comparison.neighborhood_radius == 0 {
+        return false;
+    }
+    has_close_pixel_in_neighborhood(
+        image_comparison,
+        e_imgbuf,
+        &a_imgbuf.get_pixel(x, y).0,
+        x,
+        y,
+    ) && has_close_pixel_in_neighborhood(
+        image_comparison,
+        a_imgbuf,
+        &e_imgbuf.get_pixel(x, y).0,
+        x,
+        y,
+    )
+}
+
+fn has_close_pixel_in_neighborhood(
+    image_comparison: &ImageComparison,
+    imgbuf: &RgbaImage,
+    pixel: &[u8; 4],
+    x: u32,
+    y: u32,
+) -> bool {
+    let radius: u32 = image_comparison.neighborhood_radius;
+    let (width, height) = imgbuf.dimensions();
+    let max_x: u32 = min(x.saturating_add(radius), width - 1);
+    let max_y: u32 = min(y.saturating_add(radius), height - 1);
+    for neighbor_y in y.saturating_sub(radius)..=max_y {
+        for neighbor_x in x.saturating_sub(radius)..=max_x {
+            if !image_comparison
+                .pixel_comparison
+                .differs(pixel, &imgbuf.get_pixel(neighbor_x, neighbor_y).0)
+            {
+                return true;
             }
         }
     }
+    false
+}
+
+fn color_delta(image_comparison: &ImageComparison, x: usize, y: usize) -> f64 {
+    let e: &Rgba<u8> = image_comparison
+        .expected
+        .as_rgba8()
+        .expect("msg")
+        .get_pixel(x as u32, y as u32);
+    let a: &Rgba<u8> = image_comparison
+        .actual
+        .as_rgba8()
+        .expect("msg")
+        .get_pixel(x as u32, y as u32);
+    color::color_delta(image_comparison.color_difference, &e.0, &a.0)
 }
 
-fn join_to_region(image_comparison: &mut ImageComparison, x: usize, y: usize) {
-    if is_jump_rejected(image_comparison, x, y) {
-        //println!("JumpRejected x: {} -- y: {}", x, y);
-        return;
+// cell boundaries are spread evenly, so cells differ in size by at most one pixel; there are
+// at most as many columns and rows as pixels
+fn create_difference_grid(
+    image_comparison: &ImageComparison,
+    grid_size: (u32, u32),
+    threshold: f64,
+) -> DifferenceGrid {
+    let width: usize = image_comparison.image_width as usize;
+    let height: usize = image_comparison.image_height as usize;
+    let columns: usize = (grid_size.0 as usize).clamp(1, width.max(1));
+    let rows: usize = (grid_size.1 as usize).clamp(1, height.max(1));
+    let column_of = |x: usize| -> usize { min(x * columns / width, columns - 1) };
+    let row_of = |y: usize| -> usize { min(y * rows / height, rows - 1) };
+    let mut counts: Array2<usize> = Array2::<usize>::zeros((rows, columns));
+    for (x, y) in image_comparison.difference_mask.iter_ones() {
+        counts[[row_of(y), column_of(x)]] += 1;
     }
-    image_comparison.matrix[[y, x]] = image_comparison.region_count as usize;
-    // println!(
-    //     "jtr x: {} -- y: {} = {}",
-    //     x, y, image_comparison.region_count
-    // );
-    for i in 0..image_comparison.threshold {
-        join_to_region(image_comparison, x + 1 + i as usize, y);
-        join_to_region(image_comparison, x, y + 1 + i as usize);
-        if no_sub_overflow(y, 1, i as usize) {
-            join_to_region(image_comparison, x + 1 + i as usize, y - 1 - i as usize);
-        } else {
-            //println!("JumpRejected -ov1- x: {} -- y: {}", x, y);
+    let mut cells: Vec<GridCell> = Vec::with_capacity(rows * columns);
+    for row in 0..rows {
+        for column in 0..columns {
+            // first pixel of a cell: the smallest coordinate mapped to it
+            let min_x: usize = (column * width + columns - 1) / columns;
+            let max_x: usize = ((column + 1) * width + columns - 1) / columns - 1;
+            let min_y: usize = (row * height + rows - 1) / rows;
+            let max_y: usize = ((row + 1) * height + rows - 1) / rows - 1;
+            let rectangle: Rectangle =
+                Rectangle::create_with_coordinates(min_x, min_y, max_x, max_y);
+            let difference_fraction: f64 = counts[[row, column]] as f64 / rectangle.size() as f64;
+            cells.push(GridCell {
+                column: column as u32,
+                row: row as u32,
+                rectangle,
+                difference_fraction,
+                is_highlighted: difference_fraction > threshold,
+            });
         }
-        if no_sub_overflow(x, 1, i as usize) {
-            join_to_region(image_comparison, x - 1 - i as usize, y + 1 + i as usize);
-        } else {
-            //println!("JumpRejected -ov2- x: {} -- y: {}", x, y);
-        }
-        join_to_region(image_comparison, x + 1 + i as usize, y + 1 + i as usize);
+    }
+    DifferenceGrid {
+        columns: columns as u32,
+        rows: rows as u32,
+        cells,
     }
 }
 
-fn is_jump_rejected(image_comparison: &ImageComparison, x: usize, y: usize) -> bool {
-    y >= image_comparison.matrix.nrows()
-        || x >= image_comparison.matrix.ncols()
-        || image_comparison.matrix[[y, x]] != 1
+fn create_regions(
+    image_comparison: &ImageComparison,
+    pixel_regions: &[PixelRegion],
+) -> Vec<Region> {
+    let mut regions: Vec<Region> = pixel_regions
+        .iter()
+        .map(|pixel_region| create_region(image_comparison, pixel_region))
+        .collect();
+    regions.sort_by(|first, second| second.severity_score.total_cmp(&first.severity_score));
+    regions
 }
 
-fn no_sub_overflow(value: usize, operand_1: usize, operand_2: usize) -> bool {
-    if let Some(result_1) = value.checked_sub(operand_1) {
-        if let Some(_result_2) = result_1.checked_sub(operand_2) {
-            return true;
+fn create_region(image_comparison: &ImageComparison, pixel_region: &PixelRegion) -> Region {
+    let rectangle: &Rectangle = &pixel_region.rectangle;
+    let mut pixel_count: usize = 0;
+    let mut sum_x: f64 = 0f64;
+    let mut sum_y: f64 = 0f64;
+    let mut sum_color_delta: f64 = 0f64;
+    let mut max_color_delta_of_region: f64 = 0f64;
+    let mut add_pixel = |x: usize, y: usize, color_delta: f64| {
+        pixel_count += 1;
+        sum_x += x as f64;
+        sum_y += y as f64;
+        sum_color_delta += color_delta;
+        max_color_delta_of_region = max_color_delta_of_region.max(color_delta);
+    };
+    for (x, y) in pixel_region.pixels() {
+        if !image_comparison.is_size_mismatch_area(x, y) {
+            add_pixel(x, y, color_delta(image_comparison, x, y));
         }
     }
-    false
-}
-
-fn create_rectangle(image_comparison: &ImageComparison) -> Rectangle {
-    let mut rectange = Rectangle::create_default();
-    for y in 0..image_comparison.matrix.nrows() {
-        for x in 0..image_comparison.matrix.ncols() {
-            if image_comparison.matrix[[y, x]] == image_comparison.counter as usize {
-                //println!("cr -> x: {} -- y: {}", x, y);
-                update_rectangle_creation(&mut rectange, x, y);
+    // pixels that exist in only one of the images differ completely
+    for size_mismatch_area in image_comparison.size_mismatch_areas.iter() {
+        let min_x: usize = max(rectangle.min_point.x, size_mismatch_area.min_point.x);
+        let min_y: usize = max(rectangle.min_point.y, size_mismatch_area.min_point.y);
+        let max_x: usize = min(rectangle.max_point.x, size_mismatch_area.max_point.x);
+        let max_y: usize = min(rectangle.max_point.y, size_mismatch_area.max_point.y);
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                add_pixel(
+                    x,
+                    y,
+                    color::max_color_delta(image_comparison.color_difference),
+                );
             }
         }
     }
-    rectange
+    let (centroid, mean_color_delta) = if pixel_count == 0 {
+        ((0f64, 0f64), 0f64)
+    } else {
+        (
+            (sum_x / pixel_count as f64, sum_y / pixel_count as f64),
+            sum_color_delta / pixel_count as f64,
+        )
+    };
+    let severity_score: f64 = pixel_count as f64 * mean_color_delta
+        / color::max_color_delta(image_comparison.color_difference);
+    let severity: Severity = if severity_score < image_comparison.severity_thresholds.0 {
+        Severity::Low
+    } else if severity_score < image_comparison.severity_thresholds.1 {
+        Severity::Medium
+    } else {
+        Severity::High
+    };
+    let contours: Vec<Vec<Point>> = if image_comparison.trace_contours {
+        trace_contours(pixel_region)
+    } else {
+        Vec::new()
+    };
+    Region {
+        rectangle: *rectangle,
+        contours,
+        pixel_count,
+        centroid,
+        mean_color_delta,
+        max_color_delta: max_color_delta_of_region,
+        severity_score,
+        severity,
+    }
 }
 
-fn update_rectangle_creation(rectangle: &mut Rectangle, x: usize, y: usize) {
-    if x < rectangle.min_point.x {
-        rectangle.min_point.x = x;
-    }
-    if x > rectangle.max_point.x {
-        rectangle.max_point.x = x;
-    }
-    if y < rectangle.min_point.y {
-        rectangle.min_point.y = y;
-    }
-    if y > rectangle.max_point.y {
-        rectangle.max_point.y = y;
+// one polygon per 8-connected component of the pixels of the region, traced on its runs
+fn trace_contours(pixel_region: &PixelRegion) -> Vec<Vec<Point>> {
+    // merged regions and custom groupings do not keep the runs sorted
+    let mut runs: Vec<PixelRun> = pixel_region.runs.clone();
+    runs.sort_unstable_by_key(|run| (run.y, run.x));
+    let components: Vec<usize> = label_run_components(&runs);
+    let mut is_traced: Vec<bool> = vec![false; runs.len()];
+    let mut contours: Vec<Vec<Point>> = Vec::new();
+    for (run, component) in runs.iter().zip(components.iter()) {
+        if is_traced[*component] {
+            continue;
+        }
+        is_traced[*component] = true;
+        // the first run of a component starts with its first pixel in raster order
+        let start: Point = Point::new(run.x as usize, run.y as usize);
+        let contour: Vec<Point> = contour::trace_contour(start, |x, y| {
+            x >= 0
+                && y >= 0
+                && find_run(&runs, x as u32, y as u32)
+                    .map_or(false, |index| components[index] == *component)
+        });
+        contours.push(contour);
     }
+    contours
 }
 
-fn merge_rectangles(mut rectangles: Vec<Rectangle>) -> Vec<Rectangle> {
-    let mut position: usize = 0;
-    let zero_rectangle: Rectangle = Rectangle::create_zero();
-    while position < rectangles.len() {
-        if rectangles[position].equals(&zero_rectangle) {
-            position += 1;
+// the component of each run is the index of the first run of the component; runs touch
+// when they overlap or meet diagonally in consecutive rows
+fn label_run_components(runs: &[PixelRun]) -> Vec<usize> {
+    let mut parents: Vec<usize> = (0..runs.len()).collect();
+    for (index, run) in runs.iter().enumerate() {
+        if run.y == 0 {
+            continue;
         }
-        for index in 1 + position..rectangles.len() {
-            //println!("index: {} -- position: {}", index, position);
-            let rectangle_at_position: Rectangle = rectangles[position];
-            let rectangle_at_index: Rectangle = rectangles[index];
-            if rectangle_at_index.equals(&zero_rectangle) {
-                continue;
-            }
-            if rectangle_at_position.is_overlapping(&rectangle_at_index) {
-                rectangles[position] = rectangle_at_position.merge(&rectangle_at_index);
-                rectangles[index] = Rectangle::create_zero();
-                if position != 0 {
-                    position -= 1;
-                }
+        let first_touching: usize = runs[..index]
+            .partition_point(|other| (other.y, other.x + other.length) < (run.y - 1, run.x));
+        for (other_index, other) in runs.iter().enumerate().take(index).skip(first_touching) {
+            if other.y != run.y - 1 || other.x > run.x + run.length {
+                break;
             }
+            let root: usize = find_component(&mut parents, index);
+            let other_root: usize = find_component(&mut parents, other_index);
+            parents[root.max(other_root)] = root.min(other_root);
         }
-        position += 1;
     }
-    rectangles
-        .into_iter()
-        .filter(|rectangle| !rectangle.equals(&zero_rectangle))
+    (0..runs.len())
+        .map(|index| find_component(&mut parents, index))
         .collect()
 }
 
-fn draw_rectangles(
+fn find_component(parents: &mut [usize], mut index: usize) -> usize {
+    while parents[index] != index {
+        parents[index] = parents[parents[index]];
+        index = parents[index];
+    }
+    index
+}
+
+// the index of the run that contains (x, y) in runs sorted by row
+fn find_run(runs: &[PixelRun], x: u32, y: u32) -> Option<usize> {
+    let index: usize = runs.partition_point(|run| (run.y, run.x) <= (y, x));
+    if index > 0 && runs[index - 1].y == y && x < runs[index - 1].x + runs[index - 1].length {
+        Some(index - 1)
+    } else {
+        None
+    }
+}
+
+fn percent_of_different_pixels(
     image_comparison: &ImageComparison,
-    mut rectangles: Vec<Rectangle>,
-    color: Rgba<u8>,
-) -> RgbaImage {
-    let mut result: RgbaImage = image_comparison.actual.to_rgba8();
-    let thickness: u32 = 2;
-    for rectangle in rectangles.iter_mut() {
-        for i in 0..=thickness {
-            if i > 0 {
-                rectangle.min_point.decrement();
-                rectangle.max_point.increment();
-            }
-            // println!(
-            //     "min_point_x: {:?} -- min_point_y:{:?} -- max_point_x: {:?} -- max_point_y:{:?}",
-            //     rectangle.min_point.x,
-            //     rectangle.min_point.y,
-            //     rectangle.max_point.x,
-            //     rectangle.max_point.y
-            // );
-            if !rectangle.out_of_bounds(image_comparison) {
-                draw_rectangle(&mut result, rectangle, color);
-            }
-        }
+    count_of_different_pixels: &usize,
+) -> f64 {
+    let total_pixel_count: usize = image_comparison.unmasked_pixel_count();
+    if total_pixel_count == 0 {
+        return 0f64;
     }
-    result
+    (*count_of_different_pixels as f64 / total_pixel_count as f64) * 100f64
 }
 
-fn draw_rectangle(image: &mut RgbaImage, rectangle: &Rectangle, color: Rgba<u8>) {
-    let min_point_x: u32 = rectangle.min_point.x.try_into().unwrap();
-    let min_point_y: u32 = rectangle.min_point.y.try_into().unwrap();
-    let max_point_x: u32 = rectangle.max_point.x.try_into().unwrap();
-    let max_point_y: u32 = rectangle.max_point.y.try_into().unwrap();
-    draw_line_segment(
-        image,
-        (min_point_x, min_point_y),
-        (max_point_x, min_point_y),
-        color,
-    );
-    draw_line_segment(
-        image,
-        (min_point_x, max_point_y),
-        (max_point_x, max_point_y),
-        color,
-    );
-    draw_line_segment(
-        image,
-        (min_point_x, min_point_y),
-        (min_point_x, max_point_y),
-        color,
-    );
-    draw_line_segment(
-        image,
-        (max_point_x, min_point_y),
-        (max_point_x, max_point_y),
-        color,
-    );
+fn is_reported_region(
+    image_comparison: &ImageComparison,
+    rectangle: &Rectangle,
+    pixel_count: usize,
+) -> bool {
+    if rectangle.size() < image_comparison.minimal_rectangle_size as usize
+        || rectangle.get_width() < image_comparison.minimal_region_width as usize
+        || rectangle.get_height() < image_comparison.minimal_region_height as usize
+    {
+        return false;
+    }
+    pixel_count >= image_comparison.minimal_region_pixel_count
+        && pixel_count as f64 / rectangle.size() as f64 >= image_comparison.minimal_region_density
 }
 
-fn draw_line_segment(image: &mut RgbaImage, start: (u32, u32), end: (u32, u32), color: Rgba<u8>) {
-    // vertical line segment
-    if start.0 == end.0 {
-        for y in start.1..=end.1 {
-            image.put_pixel(start.0, y, color);
-        }
+// pixels of regions that are not reported must not count towards the statistics of
+// reported regions whose rectangles overlap them
+fn clear_region(image_comparison: &mut ImageComparison, pixel_region: &PixelRegion) {
+    for (x, y) in pixel_region.pixels() {
+        image_comparison.difference_mask.set(x, y, false);
     }
-    // horizontal line segment
-    if start.1 == end.1 {
-        for x in start.0..=end.0 {
-            image.put_pixel(x, start.1, color);
+}
+
+// merges until no two rectangles are within merge_distance of each other, because a merged
+// rectangle can reach rectangles that neither of its parts reached
+pub(crate) fn merge_rectangles(
+    rectangles: Vec<Rectangle>,
+    merge_distance: usize,
+) -> Vec<Rectangle> {
+    let pixel_regions: Vec<PixelRegion> = rectangles
+        .into_iter()
+        .map(PixelRegion::create_with_rectangle)
+        .collect();
+    merge_pixel_regions(pixel_regions, merge_distance)
+        .into_iter()
+        .map(|pixel_region| pixel_region.rectangle)
+        .collect()
+}
+
+// the pixels of merged regions are joined, like their rectangles
+pub(crate) fn merge_pixel_regions(
+    mut pixel_regions: Vec<PixelRegion>,
+    merge_distance: usize,
+) -> Vec<PixelRegion> {
+    let mut is_merged: bool = true;
+    while is_merged {
+        is_merged = false;
+        let mut position: usize = 0;
+        while position < pixel_regions.len() {
+            let mut index: usize = position + 1;
+            while index < pixel_regions.len() {
+                if pixel_regions[position]
+                    .rectangle
+                    .is_within_distance(&pixel_regions[index].rectangle, merge_distance)
+                {
+                    let pixel_region_at_index: PixelRegion = pixel_regions.remove(index);
+                    pixel_regions[position].merge(pixel_region_at_index);
+                    is_merged = true;
+                } else {
+                    index += 1;
+                }
+            }
+            position += 1;
         }
     }
+    pixel_regions
 }
 
 #[cfg(test)]
 mod tests {
-    use super::merge_rectangles;
-    use crate::model::Rectangle;
+    use super::{merge_rectangles, trace_contours};
+    use crate::grouping::PixelRegion;
+    use crate::model::{Point, Rectangle};
+    #[test]
+    fn trace_contours_of_unsorted_runs() {
+        // a merged region: the diagonal pixels (4, 3) and (5, 4) come before the square
+        let mut pixel_region: PixelRegion =
+            PixelRegion::create_with_rectangle(Rectangle::create_with_coordinates(0, 0, 5, 4));
+        for (x, y) in [(4, 3), (5, 4), (0, 0), (1, 0), (0, 1), (1, 1)].iter() {
+            pixel_region.push(*x, *y);
+        }
+        let contours: Vec<Vec<(usize, usize)>> = trace_contours(&pixel_region)
+            .iter()
+            .map(|contour: &Vec<Point>| contour.iter().map(|point| (point.x, point.y)).collect())
+            .collect();
+        assert_eq!(
+            vec![
+                vec![(0, 0), (2, 0), (2, 2), (0, 2)],
+                vec![
+                    (4, 3),
+                    (5, 3),
+                    (5, 4),
+                    (6, 4),
+                    (6, 5),
+                    (5, 5),
+                    (5, 4),
+                    (4, 4)
+                ],
+            ],
+            contours
+        );
+    }
     #[test]
     fn merge_rectangles_same_coordinates() {
         let rectangle1: Rectangle = Rectangle::create_with_coordinates(1, 1, 3, 3);
@@ -349,7 +1166,7 @@ mod tests {
         let rectangle4: Rectangle = Rectangle::create_with_coordinates(1, 1, 3, 3);
         let rectangles: Vec<Rectangle> = vec![rectangle1, rectangle2, rectangle3, rectangle4];
         //println!("len b: {}", rectangles.len());
-        let rectangles: Vec<Rectangle> = merge_rectangles(rectangles);
+        let rectangles: Vec<Rectangle> = merge_rectangles(rectangles, 0);
         //println!("len a: {}", rectangles.len());
         assert_eq!(rectangles.len(), 1);
         let rectangle_expected: Rectangle = Rectangle::create_with_coordinates(1, 1, 3, 3);
@@ -363,7 +1180,7 @@ mod tests {
         let rectangle3: Rectangle = Rectangle::create_with_coordinates(1, 3, 3, 5);
         let rectangles: Vec<Rectangle> = vec![rectangle1, rectangle2, rectangle3];
         //println!("len b: {}", rectangles.len());
-        let rectangles: Vec<Rectangle> = merge_rectangles(rectangles);
+        let rectangles: Vec<Rectangle> = merge_rectangles(rectangles, 0);
         //println!("len a: {}", rectangles.len());
         assert_eq!(rectangles.len(), 1);
         let rectangle_expected: Rectangle = Rectangle::create_with_coordinates(1, 1, 3, 5);
@@ -377,7 +1194,7 @@ mod tests {
         let rectangle3: Rectangle = Rectangle::create_with_coordinates(3, 1, 5, 2);
         let rectangles: Vec<Rectangle> = vec![rectangle1, rectangle2, rectangle3];
         //println!("len b: {}", rectangles.len());
-        let rectangles: Vec<Rectangle> = merge_rectangles(rectangles);
+        let rectangles: Vec<Rectangle> = merge_rectangles(rectangles, 0);
         //println!("len a: {}", rectangles.len());
         assert_eq!(rectangles.len(), 1);
         let rectangle_expected: Rectangle = Rectangle::create_with_coordinates(1, 1, 5, 2);
@@ -391,7 +1208,7 @@ mod tests {
         let rectangle3: Rectangle = Rectangle::create_with_coordinates(5, 5, 7, 7);
         let rectangles: Vec<Rectangle> = vec![rectangle1, rectangle2, rectangle3];
         //println!("len b: {}", rectangles.len());
-        let rectangles: Vec<Rectangle> = merge_rectangles(rectangles);
+        let rectangles: Vec<Rectangle> = merge_rectangles(rectangles, 0);
         //println!("len a: {}", rectangles.len());
         assert_eq!(rectangles.len(), 1);
         let rectangle_expected: Rectangle = Rectangle::create_with_coordinates(1, 1, 7, 7);
@@ -405,11 +1222,49 @@ mod tests {
         let rectangle3: Rectangle = Rectangle::create_with_coordinates(5, 5, 6, 6);
         let rectangles: Vec<Rectangle> = vec![rectangle1, rectangle2, rectangle3];
         //println!("len b: {}", rectangles.len());
-        let rectangles: Vec<Rectangle> = merge_rectangles(rectangles);
+        let rectangles: Vec<Rectangle> = merge_rectangles(rectangles, 0);
         //println!("len a: {}", rectangles.len());
         assert_eq!(rectangles.len(), 3);
         assert!(rectangles[0].equals(&rectangle1));
         assert!(rectangles[1].equals(&rectangle2));
         assert!(rectangles[2].equals(&rectangle3));
     }
+    #[test]
+    fn merge_rectangles_chain_in_one_call() {
+        // the last rectangle only overlaps the merge of the first two
+        let rectangle1: Rectangle = Rectangle::create_with_coordinates(1, 1, 2, 2);
+        let rectangle2: Rectangle = Rectangle::create_with_coordinates(10, 1, 12, 2);
+        let rectangle3: Rectangle = Rectangle::create_with_coordinates(5, 5, 6, 6);
+        let rectangle4: Rectangle = Rectangle::create_with_coordinates(2, 1, 10, 1);
+        let rectangle5: Rectangle = Rectangle::create_with_coordinates(5, 2, 5, 5);
+        let rectangles: Vec<Rectangle> =
+            vec![rectangle1, rectangle2, rectangle3, rectangle4, rectangle5];
+        let rectangles: Vec<Rectangle> = merge_rectangles(rectangles, 0);
+        assert_eq!(rectangles.len(), 1);
+        let rectangle_expected: Rectangle = Rectangle::create_with_coordinates(1, 1, 12, 6);
+        assert!(rectangle_expected.equals(&rectangles[0]));
+    }
+    #[test]
+    fn merge_rectangles_origin_pixel() {
+        let rectangle1: Rectangle = Rectangle::create_with_coordinates(0, 0, 0, 0);
+        let rectangle2: Rectangle = Rectangle::create_with_coordinates(5, 5, 6, 6);
+        let rectangles: Vec<Rectangle> = merge_rectangles(vec![rectangle1, rectangle2], 0);
+        assert_eq!(rectangles.len(), 2);
+        assert!(rectangles[0].equals(&rectangle1));
+    }
+    #[test]
+    fn merge_rectangles_within_distance() {
+        let rectangle1: Rectangle = Rectangle::create_with_coordinates(1, 1, 2, 2);
+        let rectangle2: Rectangle = Rectangle::create_with_coordinates(5, 1, 6, 2);
+        let rectangle3: Rectangle = Rectangle::create_with_coordinates(20, 20, 21, 21);
+        let rectangles: Vec<Rectangle> =
+            merge_rectangles(vec![rectangle1, rectangle2, rectangle3], 2);
+        assert_eq!(rectangles.len(), 3);
+        let rectangles: Vec<Rectangle> =
+            merge_rectangles(vec![rectangle1, rectangle2, rectangle3], 3);
+        assert_eq!(rectangles.len(), 2);
+        let rectangle_expected: Rectangle = Rectangle::create_with_coordinates(1, 1, 6, 2);
+        assert!(rectangle_expected.equals(&rectangles[0]));
+        assert!(rectangle3.equals(&rectangles[1]));
+    }
 }
@@ -0,0 +1,497 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::bitmatrix::BitMatrix;
+use crate::model::Rectangle;
+use ndarray::Array2;
+use std::cmp::min;
+
+/// Read-only view of the pixels that differ, after ignored areas and morphological
+/// operations were applied.
+pub struct DifferenceMask<'a> {
+    bit_matrix: &'a BitMatrix,
+}
+
+impl<'a> DifferenceMask<'a> {
+    pub(crate) fn new(bit_matrix: &'a BitMatrix) -> Self {
+        Self { bit_matrix }
+    }
+    pub fn width(&self) -> usize {
+        self.bit_matrix.width()
+    }
+    pub fn height(&self) -> usize {
+        self.bit_matrix.height()
+    }
+    pub fn is_different(&self, x: usize, y: usize) -> bool {
+        self.bit_matrix.get(x, y)
+    }
+    /// Coordinates (x, y) of the different pixels in raster order.
+    pub fn different_pixels(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.bit_matrix.iter_ones()
+    }
+}
+
+/// Groups the different pixels into the reported regions.
+///
+/// Each different pixel should belong to one region. The region filters of
+/// `ImageComparisonOptions` are applied to the returned regions, and the statistics of a
+/// region are measured over its own pixels only.
+pub trait RegionGrouping: Send + Sync {
+    fn group(&self, difference_mask: &DifferenceMask) -> Vec<PixelRegion>;
+    /// Regions within this distance are merged after the region filters, like with
+    /// `merge_rectangles` and `merge_distance`; `None` keeps the grouped regions.
+    fn merge_distance(&self) -> Option<u32> {
+        None
+    }
+}
+
+impl fmt::Debug for dyn RegionGrouping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("RegionGrouping")
+    }
+}
+
+/// The default grouping: a different pixel joins the region of a different pixel up to
+/// `threshold` pixels away along its row, its column or a diagonal, and the regions are
+/// merged after the region filters like with `merge_rectangles` and `merge_distance` when
+/// `merge_distance` is set.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ThresholdGrouping {
+    pub threshold: u32,
+    pub merge_distance: Option<u32>,
+}
+
+impl RegionGrouping for ThresholdGrouping {
+    fn group(&self, difference_mask: &DifferenceMask) -> Vec<PixelRegion> {
+        group_by_threshold(difference_mask.bit_matrix, self.threshold as usize)
+    }
+    fn merge_distance(&self) -> Option<u32> {
+        self.merge_distance
+    }
+}
+
+/// One region per group of 8-connected different pixels, without merging.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ConnectedComponentGrouping;
+
+impl RegionGrouping for ConnectedComponentGrouping {
+    fn group(&self, difference_mask: &DifferenceMask) -> Vec<PixelRegion> {
+        // the neighbors one pixel away along rows, columns and diagonals are the 8 neighbors
+        group_by_threshold(difference_mask.bit_matrix, 1)
+    }
+}
+
+/// One region per cell of a grid of `cell_width` × `cell_height` pixels that contains
+/// different pixels, covering the whole cell.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct GridCellGrouping {
+    pub cell_width: u32,
+    pub cell_height: u32,
+}
+
+impl RegionGrouping for GridCellGrouping {
+    fn group(&self, difference_mask: &DifferenceMask) -> Vec<PixelRegion> {
+        let cell_width: usize = self.cell_width.max(1) as usize;
+        let cell_height: usize = self.cell_height.max(1) as usize;
+        let columns: usize = (difference_mask.width() + cell_width - 1) / cell_width;
+        let rows: usize = (difference_mask.height() + cell_height - 1) / cell_height;
+        let mut cell_regions: Array2<Option<PixelRegion>> =
+            Array2::<Option<PixelRegion>>::default((rows, columns));
+        for (x, y) in difference_mask.different_pixels() {
+            let (row, column) = (y / cell_height, x / cell_width);
+            cell_regions[[row, column]]
+                .get_or_insert_with(|| {
+                    PixelRegion::create_with_rectangle(Rectangle::create_with_coordinates(
+                        column * cell_width,
+                        row * cell_height,
+                        min((column + 1) * cell_width, difference_mask.width()) - 1,
+                        min((row + 1) * cell_height, difference_mask.height()) - 1,
+                    ))
+                })
+                .push(x, y);
+        }
+        cell_regions.into_iter().flatten().collect()
+    }
+}
+
+/// A single region enclosing all different pixels.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BoundingBoxGrouping;
+
+impl RegionGrouping for BoundingBoxGrouping {
+    fn group(&self, difference_mask: &DifferenceMask) -> Vec<PixelRegion> {
+        let mut bounding_region: Option<PixelRegion> = None;
+        for (x, y) in difference_mask.different_pixels() {
+            let pixel: Rectangle = Rectangle::create_with_coordinates(x, y, x, y);
+            let pixel_region: &mut PixelRegion =
+                bounding_region.get_or_insert_with(|| PixelRegion::create_with_rectangle(pixel));
+            pixel_region.rectangle = pixel_region.rectangle.merge(&pixel);
+            pixel_region.push(x, y);
+        }
+        bounding_region.into_iter().collect()
+    }
+}
+
+/// `length` consecutive different pixels of row `y`, starting at column `x`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PixelRun {
+    pub x: u32,
+    pub y: u32,
+    pub length: u32,
+}
+
+/// The different pixels of a region as runs of consecutive pixels of a row, and the
+/// rectangle of the region, which encloses them and can be larger, e.g. a whole grid cell.
+#[derive(Debug, Clone)]
+pub struct PixelRegion {
+    pub rectangle: Rectangle,
+    pub runs: Vec<PixelRun>,
+}
+
+impl PixelRegion {
+    /// A region of the rectangle without pixels, to which pixels can be pushed.
+    pub fn create_with_rectangle(rectangle: Rectangle) -> Self {
+        Self {
+            rectangle,
+            runs: Vec::new(),
+        }
+    }
+    /// Adds the different pixel (x, y), extending the last run when the pixel follows it.
+    /// The rectangle is not changed.
+    pub fn push(&mut self, x: usize, y: usize) {
+        let (x, y) = (x as u32, y as u32);
+        match self.runs.last_mut() {
+            Some(run) if run.y == y && run.x + run.length == x => run.length += 1,
+            _ => self.runs.push(PixelRun { x, y, length: 1 }),
+        }
+    }
+    pub fn pixel_count(&self) -> usize {
+        self.runs.iter().map(|run| run.length as usize).sum()
+    }
+    /// Coordinates (x, y) of the different pixels, run by run.
+    pub fn pixels(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.runs
+            .iter()
+            .flat_map(|run| (run.x..run.x + run.length).map(move |x| (x as usize, run.y as usize)))
+    }
+    // appends the shorter list of runs to the longer one, so that repeated merges stay linear
+    pub(crate) fn merge(&mut self, mut that: PixelRegion) {
+        self.rectangle = self.rectangle.merge(&that.rectangle);
+        if self.runs.len() < that.runs.len() {
+            std::mem::swap(&mut self.runs, &mut that.runs);
+        }
+        self.runs.extend(that.runs);
+    }
+}
+
+// the regions of the default grouping in the order of their first pixel, before merging,
+// with the runs of each region sorted by row
+pub(crate) fn group_by_threshold(bit_matrix: &BitMatrix, threshold: usize) -> Vec<PixelRegion> {
+    let mut threshold_labeling: ThresholdLabeling =
+        ThresholdLabeling::new(bit_matrix.width(), threshold, true);
+    let mut different_pixels: Vec<bool> = vec![false; bit_matrix.width()];
+    for y in 0..bit_matrix.height() {
+        for (x, is_different) in different_pixels.iter_mut().enumerate() {
+            *is_different = bit_matrix.get(x, y);
+        }
+        threshold_labeling.push_row(&different_pixels);
+    }
+    threshold_labeling.finish()
+}
+
+// streaming labeling with a union-find over provisional labels, which keeps only the labels
+// of the last threshold rows. A different pixel joins the different pixels up to threshold
+// pixels to its left, above it and diagonally above it, so every pair of pixels that are
+// joined along a row, column or diagonal is seen from the later one. The rectangle and,
+// when collecting runs, the runs of a label are merged into its root on union.
+pub(crate) struct ThresholdLabeling {
+    width: usize,
+    threshold: usize,
+    y: usize,
+    is_collecting_runs: bool,
+    previous_rows: VecDeque<Vec<u32>>,
+    // label 0 marks equal pixels
+    parents: Vec<u32>,
+    pixel_regions: Vec<PixelRegion>,
+}
+
+impl ThresholdLabeling {
+    pub(crate) fn new(width: usize, threshold: usize, is_collecting_runs: bool) -> Self {
+        Self {
+            width,
+            threshold,
+            y: 0,
+            is_collecting_runs,
+            previous_rows: VecDeque::new(),
+            parents: vec![0],
+            pixel_regions: vec![PixelRegion::create_with_rectangle(Rectangle::create_zero())],
+        }
+    }
+    // returns the number of different pixels of the row
+    pub(crate) fn push_row(&mut self, different_pixels: &[bool]) -> usize {
+        let mut labels: Vec<u32> = vec![0; self.width];
+        let mut count_of_different_pixels: usize = 0;
+        for x in 0..self.width {
+            if !different_pixels[x] {
+                continue;
+            }
+            count_of_different_pixels += 1;
+            let mut root: u32 = 0;
+            // the pixels to the left within threshold are already joined with each other
+            if let Some(distance) = (1..=self.threshold.min(x)).find(|i| labels[x - i] != 0) {
+                root = self.join(root, labels[x - distance]);
+            }
+            for distance in 1..=self.previous_rows.len() {
+                let row_index: usize = self.previous_rows.len() - distance;
+                let neighbors: [Option<usize>; 3] = [
+                    x.checked_sub(distance),
+                    Some(x),
+                    Some(x + distance).filter(|neighbor_x| *neighbor_x < self.width),
+                ];
+                for neighbor_x in neighbors.iter().flatten() {
+                    let label: u32 = self.previous_rows[row_index][*neighbor_x];
+                    if label != 0 {
+                        root = self.join(root, label);
+                    }
+                }
+            }
+            if root == 0 {
+                root = self.parents.len() as u32;
+                self.parents.push(root);
+                self.pixel_regions.push(PixelRegion::create_with_rectangle(
+                    Rectangle::create_with_coordinates(x, self.y, x, self.y),
+                ));
+            }
+            labels[x] = root;
+        }
+        self.add_runs(&labels);
+        if self.threshold > 0 {
+            if self.previous_rows.len() == self.threshold {
+                self.previous_rows.pop_front();
+            }
+            self.previous_rows.push_back(labels);
+        }
+        self.y += 1;
+        count_of_different_pixels
+    }
+    // the regions in the order of their first pixel, because the smallest label of a region
+    // is the label of its first pixel and stays its root
+    pub(crate) fn finish(mut self) -> Vec<PixelRegion> {
+        let mut pixel_regions: Vec<PixelRegion> = Vec::new();
+        for label in 1..self.parents.len() {
+            if self.parents[label] == label as u32 {
+                let mut pixel_region: PixelRegion = std::mem::replace(
+                    &mut self.pixel_regions[label],
+                    PixelRegion::create_with_rectangle(Rectangle::create_zero()),
+                );
+                pixel_region.runs.sort_unstable_by_key(|run| (run.y, run.x));
+                pixel_regions.push(pixel_region);
+            }
+        }
+        pixel_regions
+    }
+    // consecutive pixels of the row with the same root form one run
+    fn add_runs(&mut self, labels: &[u32]) {
+        let mut x: usize = 0;
+        while x < self.width {
+            if labels[x] == 0 {
+                x += 1;
+                continue;
+            }
+            let root: u32 = self.find(labels[x]);
+            let start: usize = x;
+            while x < self.width && labels[x] != 0 && self.find(labels[x]) == root {
+                x += 1;
+            }
+            let pixel_region: &mut PixelRegion = &mut self.pixel_regions[root as usize];
+            pixel_region.rectangle =
+                pixel_region
+                    .rectangle
+                    .merge(&Rectangle::create_with_coordinates(
+                        start,
+                        self.y,
+                        x - 1,
+                        self.y,
+                    ));
+            if self.is_collecting_runs {
+                pixel_region.runs.push(PixelRun {
+                    x: start as u32,
+                    y: self.y as u32,
+                    length: (x - start) as u32,
+                });
+            }
+        }
+    }
+    fn join(&mut self, root: u32, label: u32) -> u32 {
+        if root == 0 {
+            self.find(label)
+        } else {
+            self.union(root, label)
+        }
+    }
+    fn find(&mut self, label: u32) -> u32 {
+        let mut root: u32 = label;
+        while self.parents[root as usize] != root {
+            root = self.parents[root as usize];
+        }
+        let mut label: u32 = label;
+        while self.parents[label as usize] != root {
+            let parent: u32 = self.parents[label as usize];
+            self.parents[label as usize] = root;
+            label = parent;
+        }
+        root
+    }
+    fn union(&mut self, root: u32, label: u32) -> u32 {
+        let other_root: u32 = self.find(label);
+        if other_root == root {
+            return root;
+        }
+        let (root, other_root) = (root.min(other_root), root.max(other_root));
+        self.parents[other_root as usize] = root;
+        let other_region: PixelRegion = std::mem::replace(
+            &mut self.pixel_regions[other_root as usize],
+            PixelRegion::create_with_rectangle(Rectangle::create_zero()),
+        );
+        self.pixel_regions[root as usize].merge(other_region);
+        root
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        group_by_threshold, BoundingBoxGrouping, ConnectedComponentGrouping, DifferenceMask,
+        GridCellGrouping, PixelRegion, PixelRun, RegionGrouping, ThresholdGrouping,
+    };
+    use crate::bitmatrix::BitMatrix;
+    use crate::model::{Point, Rectangle};
+    // every different pixel belongs to one region inside its rectangle
+    fn group(
+        region_grouping: &dyn RegionGrouping,
+        pixels: &[(usize, usize)],
+    ) -> Vec<(usize, usize, usize, usize)> {
+        let mut bit_matrix: BitMatrix = BitMatrix::new(10, 6);
+        for (x, y) in pixels.iter() {
+            bit_matrix.set(*x, *y, true);
+        }
+        let pixel_regions: Vec<PixelRegion> =
+            region_grouping.group(&DifferenceMask::new(&bit_matrix));
+        let mut grouped_pixels: Vec<(usize, usize)> = pixel_regions
+            .iter()
+            .flat_map(|pixel_region| pixel_region.pixels())
+            .collect();
+        grouped_pixels.sort();
+        let mut different_pixels: Vec<(usize, usize)> = pixels.to_vec();
+        different_pixels.sort();
+        assert_eq!(different_pixels, grouped_pixels);
+        let mut rectangles: Vec<(usize, usize, usize, usize)> = pixel_regions
+            .iter()
+            .map(|pixel_region: &PixelRegion| {
+                assert!(pixel_region
+                    .pixels()
+                    .all(|(x, y)| pixel_region.rectangle.contains_point(&Point::new(x, y))));
+                (
+                    pixel_region.rectangle.min_point.x,
+                    pixel_region.rectangle.min_point.y,
+                    pixel_region.rectangle.max_point.x,
+                    pixel_region.rectangle.max_point.y,
+                )
+            })
+            .collect();
+        rectangles.sort();
+        rectangles
+    }
+    const PIXELS: [(usize, usize); 4] = [(0, 0), (1, 1), (4, 1), (9, 5)];
+    #[test]
+    fn threshold_grouping() {
+        let threshold_grouping: ThresholdGrouping = ThresholdGrouping {
+            threshold: 3,
+            merge_distance: None,
+        };
+        assert_eq!(
+            vec![(0, 0, 4, 1), (9, 5, 9, 5)],
+            group(&threshold_grouping, &PIXELS)
+        );
+    }
+    #[test]
+    fn group_by_threshold_collects_pixels() {
+        let mut bit_matrix: BitMatrix = BitMatrix::new(10, 6);
+        for (x, y) in PIXELS.iter() {
+            bit_matrix.set(*x, *y, true);
+        }
+        let pixel_regions: Vec<PixelRegion> = group_by_threshold(&bit_matrix, 3);
+        assert_eq!(2, pixel_regions.len());
+        let pixels: Vec<(usize, usize)> = pixel_regions[0].pixels().collect();
+        assert_eq!(vec![(0, 0), (1, 1), (4, 1)], pixels);
+        assert!(pixel_regions[0]
+            .rectangle
+            .equals(&Rectangle::create_with_coordinates(0, 0, 4, 1)));
+        let pixels: Vec<(usize, usize)> = pixel_regions[1].pixels().collect();
+        assert_eq!(vec![(9, 5)], pixels);
+    }
+    #[test]
+    fn group_by_threshold_joins_from_later_pixel() {
+        // (0, 2) reaches (1, 2) only along its row, (1, 0) reaches (1, 2) along its column
+        let mut bit_matrix: BitMatrix = BitMatrix::new(3, 3);
+        for (x, y) in [(1, 0), (0, 2), (1, 2)].iter() {
+            bit_matrix.set(*x, *y, true);
+        }
+        let pixel_regions: Vec<PixelRegion> = group_by_threshold(&bit_matrix, 2);
+        assert_eq!(1, pixel_regions.len());
+        assert_eq!(3, pixel_regions[0].pixel_count());
+    }
+    #[test]
+    fn group_by_threshold_collects_runs() {
+        let mut bit_matrix: BitMatrix = BitMatrix::new(6, 2);
+        for (x, y) in [(1, 0), (2, 0), (3, 0), (5, 0), (0, 1), (1, 1)].iter() {
+            bit_matrix.set(*x, *y, true);
+        }
+        let pixel_regions: Vec<PixelRegion> = group_by_threshold(&bit_matrix, 2);
+        assert_eq!(1, pixel_regions.len());
+        assert_eq!(
+            vec![
+                PixelRun {
+                    x: 1,
+                    y: 0,
+                    length: 3
+                },
+                PixelRun {
+                    x: 5,
+                    y: 0,
+                    length: 1
+                },
+                PixelRun {
+                    x: 0,
+                    y: 1,
+                    length: 2
+                },
+            ],
+            pixel_regions[0].runs
+        );
+        assert_eq!(6, pixel_regions[0].pixel_count());
+    }
+    #[test]
+    fn connected_component_grouping() {
+        assert_eq!(
+            vec![(0, 0, 1, 1), (4, 1, 4, 1), (9, 5, 9, 5)],
+            group(&ConnectedComponentGrouping, &PIXELS)
+        );
+    }
+    #[test]
+    fn grid_cell_grouping() {
+        let grid_cell_grouping: GridCellGrouping = GridCellGrouping {
+            cell_width: 4,
+            cell_height: 4,
+        };
+        assert_eq!(
+            vec![(0, 0, 3, 3), (4, 0, 7, 3), (8, 4, 9, 5)],
+            group(&grid_cell_grouping, &PIXELS)
+        );
+    }
+    #[test]
+    fn bounding_box_grouping() {
+        assert_eq!(vec![(0, 0, 9, 5)], group(&BoundingBoxGrouping, &PIXELS));
+        assert!(group(&BoundingBoxGrouping, &[]).is_empty());
+    }
+}
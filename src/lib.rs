@@ -1,6 +1,55 @@
+mod batch;
+mod bitmatrix;
+mod color;
+mod comparator;
+mod contour;
 mod core;
+mod grouping;
 mod model;
+mod morphology;
+mod pixel;
+mod render;
+mod report;
+mod tiled;
 
+pub use crate::batch::compare_directories;
+pub use crate::batch::BatchResult;
+pub use crate::batch::ComparisonPair;
+pub use crate::comparator::Comparator;
 pub use crate::core::compare_images;
+pub use crate::core::compare_images_with_options;
+pub use crate::core::images_are_identical;
+pub use crate::grouping::BoundingBoxGrouping;
+pub use crate::grouping::ConnectedComponentGrouping;
+pub use crate::grouping::DifferenceMask;
+pub use crate::grouping::GridCellGrouping;
+pub use crate::grouping::PixelRegion;
+pub use crate::grouping::PixelRun;
+pub use crate::grouping::RegionGrouping;
+pub use crate::grouping::ThresholdGrouping;
+pub use crate::model::ColorDifference;
+pub use crate::model::DeltaEStatistics;
+pub use crate::model::DifferenceGrid;
+pub use crate::model::GridCell;
+pub use crate::model::ImageComparisonOptions;
 pub use crate::model::ImageComparisonResult;
 pub use crate::model::ImageComparisonState;
+pub use crate::model::MorphologicalOperation;
+pub use crate::model::Point;
+pub use crate::model::Rectangle;
+pub use crate::model::Region;
+pub use crate::model::RenderStyle;
+pub use crate::model::Severity;
+pub use crate::model::SizeMismatchPolicy;
+pub use crate::pixel::DeltaEPixelComparator;
+pub use crate::pixel::EuclideanPixelComparator;
+pub use crate::pixel::ExactPixelComparator;
+pub use crate::pixel::LuminancePixelComparator;
+pub use crate::pixel::PixelComparator;
+pub use crate::pixel::TolerancePixelComparator;
+pub use crate::report::junit_report;
+pub use crate::report::markdown_pair_report;
+pub use crate::report::markdown_report;
+pub use crate::report::tap_report;
+pub use crate::report::write_html_report;
+pub use crate::tiled::compare_images_tiled;
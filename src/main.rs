@@ -1,33 +1,271 @@
 use image::ImageFormat;
-use image_compare::{compare_images, ImageComparisonResult, ImageComparisonState};
+use image_compare::{
+    compare_directories, junit_report, markdown_pair_report, markdown_report, tap_report,
+    write_html_report, BatchResult, ComparisonPair, ImageComparisonOptions, ImageComparisonState,
+    RenderStyle,
+};
 use std::{
-    fs::remove_file,
+    env, fs, io,
     path::{Path, PathBuf},
+    process::ExitCode,
 };
-fn main() -> std::io::Result<()> {
-    let mut expected: PathBuf = PathBuf::new();
-    expected.push("C:\\Temp\\expected.png");
-    let mut actual: PathBuf = PathBuf::new();
-    actual.push("C:\\Temp\\actual.png");
-    let result: ImageComparisonResult = compare_images(expected.as_os_str(), actual.as_os_str());
-    match result.image_comparison_state {
-        ImageComparisonState::FormatNotSupported => (),
-        ImageComparisonState::ColorTypeNotSupported => (),
-        ImageComparisonState::SizeMismatch => (),
-        ImageComparisonState::Mismatch => {
-            println!("expected and actual images are not matching");
-            let mut result_image_path: PathBuf = PathBuf::new();
-            result_image_path.push("C:\\Temp\\result.png");
-            if Path::new(result_image_path.as_os_str()).try_exists()? {
-                remove_file(&result_image_path)?;
+
+const USAGE: &str = "usage: image_compare <expected> <actual> [options]
+
+<expected> and <actual> are two PNG images, or two directories whose images are compared
+by file name.
+
+options:
+  --result <path>              write the result image of two compared images
+  --diff-directory <directory> write the result images of the mismatching pairs
+  --report junit=<path>        write a JUnit XML report
+  --report tap[=<path>]        write a TAP report, to the standard output without a path
+  --report html=<directory>    write an HTML report
+  --report markdown[=<path>]   write a Markdown summary, to the standard output without a
+                               path
+  -h, --help                   print this help
+
+Without --diff-directory the result images of the mismatching pairs are written for the
+first JUnit or Markdown report, to the <name>-diff directory next to it, or to
+markdown-diff for a Markdown report on the standard output.
+
+The status lines go to the standard error when a report goes to the standard output.
+The exit code is 0 when all images match, 1 when they do not and 2 on errors.";
+
+enum Report {
+    JUnit(PathBuf),
+    Tap(Option<PathBuf>),
+    Html(PathBuf),
+    Markdown(Option<PathBuf>),
+}
+
+struct Arguments {
+    expected: PathBuf,
+    actual: PathBuf,
+    result_image: Option<PathBuf>,
+    diff_directory: Option<PathBuf>,
+    reports: Vec<Report>,
+}
+
+fn main() -> ExitCode {
+    let arguments: Vec<String> = env::args().skip(1).collect();
+    let arguments: Arguments = match parse_arguments(&arguments) {
+        Ok(Some(arguments)) => arguments,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    match run(&arguments) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(1),
+        Err(error) => {
+            eprintln!("error: {}", error);
+            ExitCode::from(2)
+        }
+    }
+}
+
+// None when the help is requested
+fn parse_arguments(arguments: &[String]) -> Result<Option<Arguments>, String> {
+    let mut paths: Vec<PathBuf> = Vec::new();
+    let mut result_image: Option<PathBuf> = None;
+    let mut diff_directory: Option<PathBuf> = None;
+    let mut reports: Vec<Report> = Vec::new();
+    let mut iterator = arguments.iter();
+    while let Some(argument) = iterator.next() {
+        let mut value = |option: &str| -> Result<String, String> {
+            iterator
+                .next()
+                .cloned()
+                .ok_or_else(|| format!("missing value of {}", option))
+        };
+        match argument.as_str() {
+            "--result" => result_image = Some(PathBuf::from(value(argument)?)),
+            "--diff-directory" => diff_directory = Some(PathBuf::from(value(argument)?)),
+            "--report" => reports.push(parse_report(&value(argument)?)?),
+            "-h" | "--help" => return Ok(None),
+            _ if argument.starts_with("--") => return Err(format!("unknown option: {}", argument)),
+            _ => paths.push(PathBuf::from(argument)),
+        }
+    }
+    if paths.len() != 2 {
+        return Err(String::from("expected and actual images are required"));
+    }
+    let actual: PathBuf = paths.pop().unwrap();
+    let expected: PathBuf = paths.pop().unwrap();
+    Ok(Some(Arguments {
+        expected,
+        actual,
+        result_image,
+        diff_directory,
+        reports,
+    }))
+}
+
+fn parse_report(value: &str) -> Result<Report, String> {
+    let (format, path) = match value.split_once('=') {
+        Some((format, path)) => (format, Some(PathBuf::from(path))),
+        None => (value, None),
+    };
+    match (format, path) {
+        ("junit", Some(path)) => Ok(Report::JUnit(path)),
+        ("tap", path) => Ok(Report::Tap(path)),
+        ("html", Some(path)) => Ok(Report::Html(path)),
+        ("markdown", path) => Ok(Report::Markdown(path)),
+        ("junit", None) | ("html", None) => Err(format!("missing path of the {} report", format)),
+        _ => Err(format!("unknown report: {}", value)),
+    }
+}
+
+// returns whether all images match
+fn run(arguments: &Arguments) -> io::Result<bool> {
+    for path in [&arguments.expected, &arguments.actual] {
+        if !path.try_exists()? {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} not found", path.display()),
+            ));
+        }
+    }
+    let is_batch: bool = arguments.expected.is_dir();
+    if is_batch != arguments.actual.is_dir() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "expected and actual must both be images or both be directories",
+        ));
+    }
+    let options: ImageComparisonOptions = ImageComparisonOptions::default();
+    let diff_directory: Option<PathBuf> = diff_directory(arguments);
+    let mut batch: BatchResult = if is_batch {
+        if arguments.result_image.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "--result needs two images, use --diff-directory for directories",
+            ));
+        }
+        compare_directories(
+            &arguments.expected,
+            &arguments.actual,
+            &options,
+            diff_directory.as_deref(),
+        )?
+    } else {
+        // a single comparison is reported as a batch of one pair
+        let pair: ComparisonPair =
+            ComparisonPair::compare(&arguments.expected, &arguments.actual, &options);
+        if pair.result.image_comparison_state == ImageComparisonState::FormatNotSupported {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "expected and actual must be PNG images",
+            ));
+        }
+        let mut batch: BatchResult = BatchResult {
+            expected_directory: parent_directory(&arguments.expected),
+            actual_directory: parent_directory(&arguments.actual),
+            pairs: vec![pair],
+            missing_actual_images: Vec::new(),
+            unexpected_actual_images: Vec::new(),
+        };
+        if let Some(diff_directory) = &diff_directory {
+            batch.write_diff_images(diff_directory)?;
+        }
+        batch
+    };
+    if let Some(result_image_path) = &arguments.result_image {
+        write_result_image(&mut batch.pairs[0], result_image_path)?;
+    }
+    // the status lines must not mix with a report on the standard output
+    let is_report_on_stdout: bool = arguments
+        .reports
+        .iter()
+        .any(|report| matches!(report, Report::Tap(None) | Report::Markdown(None)));
+    for pair in batch.pairs.iter() {
+        let status: String = match pair.result.image_comparison_state {
+            ImageComparisonState::Match => format!("{}: images are matching", pair.name),
+            ImageComparisonState::Mismatch => format!("{}: images are not matching", pair.name),
+            _ => format!("{}: {:?}", pair.name, pair.result.image_comparison_state),
+        };
+        print_status(&status, is_report_on_stdout);
+    }
+    for report in arguments.reports.iter() {
+        match report {
+            Report::JUnit(path) => fs::write(path, junit_report(&batch))?,
+            Report::Tap(Some(path)) => fs::write(path, tap_report(&batch))?,
+            Report::Tap(None) => print!("{}", tap_report(&batch)),
+            Report::Html(directory) => {
+                let report_path: PathBuf = write_html_report(&batch, directory)?;
+                print_status(
+                    &format!("html report: {}", report_path.display()),
+                    is_report_on_stdout,
+                );
             }
-            if let Some(result_image) = result.result_image {
-                result_image
-                    .save_with_format(result_image_path, ImageFormat::Png)
-                    .unwrap();
+            Report::Markdown(Some(path)) => {
+                fs::write(path, markdown(&batch, is_batch, &parent_directory(path)))?
             }
+            Report::Markdown(None) => print!("{}", markdown(&batch, is_batch, Path::new("."))),
         }
-        ImageComparisonState::Match => println!("expected and actual images are matching"),
+    }
+    Ok(batch.is_success())
+}
+
+// the --diff-directory, or next to a JUnit or Markdown report, which link the diff images
+fn diff_directory(arguments: &Arguments) -> Option<PathBuf> {
+    if arguments.diff_directory.is_some() {
+        return arguments.diff_directory.clone();
+    }
+    arguments.reports.iter().find_map(|report| match report {
+        Report::JUnit(path) | Report::Markdown(Some(path)) => Some(report_diff_directory(path)),
+        Report::Markdown(None) => Some(PathBuf::from("markdown-diff")),
+        _ => None,
+    })
+}
+
+// e.g. reports/junit-diff for reports/junit.xml
+fn report_diff_directory(report_path: &Path) -> PathBuf {
+    let name: String = match report_path.file_stem() {
+        Some(stem) => stem.to_string_lossy().into_owned(),
+        None => String::from("report"),
+    };
+    parent_directory(report_path).join(format!("{}-diff", name))
+}
+
+fn print_status(status: &str, is_report_on_stdout: bool) {
+    if is_report_on_stdout {
+        eprintln!("{}", status);
+    } else {
+        println!("{}", status);
+    }
+}
+
+// a single comparison is summarized without the batch statistics
+fn markdown(batch: &BatchResult, is_batch: bool, base_directory: &Path) -> String {
+    if is_batch {
+        return markdown_report(batch, base_directory);
+    }
+    markdown_pair_report(&batch.pairs[0], base_directory)
+}
+
+fn write_result_image(pair: &mut ComparisonPair, result_image_path: &Path) -> io::Result<()> {
+    if result_image_path.try_exists()? {
+        fs::remove_file(result_image_path)?;
+    }
+    if let Some(result_image) = pair.result.render(RenderStyle::Outline) {
+        result_image
+            .save_with_format(result_image_path, ImageFormat::Png)
+            .map_err(|error| io::Error::new(io::ErrorKind::Other, error))?;
+        pair.diff_image = Some(result_image_path.to_path_buf());
     }
     Ok(())
 }
+
+fn parent_directory(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(parent) => parent.to_path_buf(),
+        None => PathBuf::new(),
+    }
+}
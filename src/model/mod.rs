@@ -1,39 +1,560 @@
 #![allow(dead_code)]
-use image::{DynamicImage, GenericImageView, RgbaImage};
-use ndarray::Array2;
+use crate::bitmatrix::BitMatrix;
+use crate::grouping::RegionGrouping;
+use crate::pixel::{PixelComparator, PixelComparison};
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, Rgba};
 use std::cmp::{max, min};
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
 
 pub(crate) struct ImageComparison {
     pub(crate) threshold: u32,
     pub(crate) allowing_percent_of_different_pixels: f64,
-    pub(crate) counter: u32,
-    pub(crate) region_count: u32,
     pub(crate) minimal_rectangle_size: u32,
-    pub(crate) expected: DynamicImage,
+    pub(crate) minimal_region_pixel_count: usize,
+    pub(crate) minimal_region_width: u32,
+    pub(crate) minimal_region_height: u32,
+    pub(crate) minimal_region_density: f64,
+    pub(crate) expected: Arc<DynamicImage>,
     pub(crate) actual: DynamicImage,
     pub(crate) image_width: u32,
     pub(crate) image_height: u32,
-    pub(crate) matrix: Array2<usize>,
+    pub(crate) difference_mask: BitMatrix,
+    pub(crate) mask: Option<BitMatrix>,
+    pub(crate) shade_masked_area: bool,
+    pub(crate) color_key: Option<BitMatrix>,
+    pub(crate) included_areas: Vec<Rectangle>,
+    pub(crate) crop_to_included_areas: bool,
+    pub(crate) crop_margin: u32,
+    pub(crate) size_mismatch_areas: Vec<Rectangle>,
+    pub(crate) size_mismatch_policy: SizeMismatchPolicy,
+    pub(crate) neighborhood_radius: u32,
+    pub(crate) color_difference: ColorDifference,
+    pub(crate) pixel_comparison: PixelComparison,
+    // accumulated while the pixels are compared, the ΔE of single pixels is not kept
+    pub(crate) delta_e: Option<DeltaEStatistics>,
+    pub(crate) severity_thresholds: (f64, f64),
+    pub(crate) severity_colors: bool,
+    pub(crate) truncated_area: Option<Rectangle>,
+    pub(crate) merge_rectangles: bool,
+    pub(crate) merge_distance: u32,
+    pub(crate) region_grouping: Option<Arc<dyn RegionGrouping>>,
+    pub(crate) trace_contours: bool,
+    pub(crate) morphological_operations: Vec<MorphologicalOperation>,
+    pub(crate) morphology_kernel_size: u32,
+    pub(crate) exposed_areas: Vec<Rectangle>,
+    pub(crate) difference_percent: f64,
+    pub(crate) grid: Option<DifferenceGrid>,
+}
+
+// allocations of a comparison that can be reused by the next comparison
+#[derive(Default)]
+pub(crate) struct ComparisonBuffers {
+    pub(crate) difference_mask: Option<BitMatrix>,
+}
+
+// the parts of a comparison that a mismatching result keeps for rendering
+pub(crate) struct RenderData {
+    pub(crate) expected: Arc<DynamicImage>,
+    pub(crate) actual: DynamicImage,
+    pub(crate) image_width: u32,
+    pub(crate) image_height: u32,
+    pub(crate) difference_mask: BitMatrix,
+    pub(crate) mask: Option<BitMatrix>,
+    pub(crate) shade_masked_area: bool,
+    pub(crate) color_key: Option<BitMatrix>,
+    pub(crate) included_areas: Vec<Rectangle>,
+    pub(crate) crop_to_included_areas: bool,
+    pub(crate) crop_margin: u32,
+    pub(crate) size_mismatch_areas: Vec<Rectangle>,
+    pub(crate) exposed_areas: Vec<Rectangle>,
+    pub(crate) truncated_area: Option<Rectangle>,
+    pub(crate) color_difference: ColorDifference,
+    pub(crate) severity_colors: bool,
+    pub(crate) trace_contours: bool,
+    pub(crate) grid: Option<DifferenceGrid>,
+}
+
+impl fmt::Debug for ImageComparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ImageComparison")
+            .field("image_width", &self.image_width)
+            .field("image_height", &self.image_height)
+            .field("difference_percent", &self.difference_percent)
+            .finish()
+    }
+}
+
+impl fmt::Debug for RenderData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RenderData")
+            .field("image_width", &self.image_width)
+            .field("image_height", &self.image_height)
+            .finish()
+    }
+}
+
+impl RenderData {
+    pub(crate) fn create_from(image_comparison: ImageComparison) -> Self {
+        Self {
+            expected: image_comparison.expected,
+            actual: image_comparison.actual,
+            image_width: image_comparison.image_width,
+            image_height: image_comparison.image_height,
+            difference_mask: image_comparison.difference_mask,
+            mask: image_comparison.mask,
+            shade_masked_area: image_comparison.shade_masked_area,
+            color_key: image_comparison.color_key,
+            included_areas: image_comparison.included_areas,
+            crop_to_included_areas: image_comparison.crop_to_included_areas,
+            crop_margin: image_comparison.crop_margin,
+            size_mismatch_areas: image_comparison.size_mismatch_areas,
+            exposed_areas: image_comparison.exposed_areas,
+            truncated_area: image_comparison.truncated_area,
+            color_difference: image_comparison.color_difference,
+            severity_colors: image_comparison.severity_colors,
+            trace_contours: image_comparison.trace_contours,
+            grid: image_comparison.grid,
+        }
+    }
+    pub(crate) fn is_masked(&self, x: usize, y: usize) -> bool {
+        match &self.mask {
+            Some(mask) => mask.get(x, y),
+            None => false,
+        }
+    }
+    pub(crate) fn is_color_keyed(&self, x: usize, y: usize) -> bool {
+        match &self.color_key {
+            Some(color_key) => color_key.get(x, y),
+            None => false,
+        }
+    }
+    pub(crate) fn is_different(&self, x: usize, y: usize) -> bool {
+        self.difference_mask.get(x, y)
+    }
+    pub(crate) fn is_size_mismatch_area(&self, x: usize, y: usize) -> bool {
+        let point: Point = Point::new(x, y);
+        self.size_mismatch_areas
+            .iter()
+            .any(|size_mismatch_area| size_mismatch_area.contains_point(&point))
+    }
 }
 
 impl ImageComparison {
-    pub(crate) fn new(expected: DynamicImage, actual: DynamicImage) -> Self {
+    pub(crate) fn new(
+        expected: Arc<DynamicImage>,
+        actual: DynamicImage,
+        difference_mask: BitMatrix,
+        pixel_comparison: PixelComparison,
+    ) -> Self {
         Self {
             threshold: 5,
-            counter: 2,
-            region_count: 2,
             minimal_rectangle_size: 1,
+            minimal_region_pixel_count: 0,
+            minimal_region_width: 0,
+            minimal_region_height: 0,
+            minimal_region_density: 0f64,
             image_width: expected.dimensions().0,
             image_height: expected.dimensions().1,
-            matrix: Array2::<usize>::zeros((
-                expected.dimensions().1 as usize,
-                expected.dimensions().0 as usize,
-            )),
+            difference_mask,
             allowing_percent_of_different_pixels: 0f64,
+            mask: None,
+            shade_masked_area: false,
+            color_key: None,
+            included_areas: Vec::new(),
+            crop_to_included_areas: false,
+            crop_margin: 0,
+            size_mismatch_areas: Vec::new(),
+            size_mismatch_policy: SizeMismatchPolicy::Reject,
+            neighborhood_radius: 0,
+            color_difference: ColorDifference::Rgb,
+            pixel_comparison,
+            delta_e: None,
+            severity_thresholds: (10f64, 100f64),
+            severity_colors: false,
+            truncated_area: None,
+            merge_rectangles: true,
+            merge_distance: 0,
+            region_grouping: None,
+            trace_contours: false,
+            morphological_operations: Vec::new(),
+            morphology_kernel_size: 3,
+            exposed_areas: Vec::new(),
+            difference_percent: 0f64,
+            grid: None,
             expected,
             actual,
         }
     }
+    // the difference mask of the buffers is reused when it has the dimensions of the images
+    pub(crate) fn create_with_options(
+        expected: Arc<DynamicImage>,
+        actual: DynamicImage,
+        options: &ImageComparisonOptions,
+        pixel_comparison: PixelComparison,
+        buffers: &mut ComparisonBuffers,
+    ) -> Self {
+        let (width, height) = expected.dimensions();
+        let difference_mask: BitMatrix = match buffers.difference_mask.take() {
+            Some(mut difference_mask)
+                if difference_mask.width() == width as usize
+                    && difference_mask.height() == height as usize =>
+            {
+                difference_mask.clear();
+                difference_mask
+            }
+            _ => BitMatrix::new(width as usize, height as usize),
+        };
+        let mut image_comparison: ImageComparison =
+            Self::new(expected, actual, difference_mask, pixel_comparison);
+        image_comparison.threshold = options.threshold;
+        image_comparison.minimal_rectangle_size = options.minimal_rectangle_size;
+        image_comparison.minimal_region_pixel_count = options.minimal_region_pixel_count;
+        image_comparison.minimal_region_width = options.minimal_region_width;
+        image_comparison.minimal_region_height = options.minimal_region_height;
+        image_comparison.minimal_region_density = options.minimal_region_density;
+        image_comparison.allowing_percent_of_different_pixels =
+            options.allowing_percent_of_different_pixels;
+        image_comparison.shade_masked_area = options.shade_masked_area;
+        image_comparison.included_areas = options.included_areas.clone();
+        image_comparison.crop_to_included_areas = options.crop_to_included_areas;
+        image_comparison.crop_margin = options.crop_margin;
+        image_comparison.size_mismatch_policy = options.size_mismatch_policy;
+        image_comparison.neighborhood_radius = options.neighborhood_radius;
+        image_comparison.color_difference = options.color_difference;
+        image_comparison.severity_thresholds = options.severity_thresholds;
+        image_comparison.severity_colors = options.severity_colors;
+        image_comparison.merge_rectangles = options.merge_rectangles;
+        image_comparison.merge_distance = options.merge_distance;
+        image_comparison.region_grouping = options.region_grouping.clone();
+        image_comparison.trace_contours = options.trace_contours;
+        image_comparison.morphological_operations = options.morphological_operations.clone();
+        image_comparison.morphology_kernel_size = options.morphology_kernel_size;
+        image_comparison
+    }
+    pub(crate) fn is_masked(&self, x: usize, y: usize) -> bool {
+        match &self.mask {
+            Some(mask) => mask.get(x, y),
+            None => false,
+        }
+    }
+    pub(crate) fn is_color_keyed(&self, x: usize, y: usize) -> bool {
+        match &self.color_key {
+            Some(color_key) => color_key.get(x, y),
+            None => false,
+        }
+    }
+    pub(crate) fn is_included(&self, x: usize, y: usize) -> bool {
+        if self.included_areas.is_empty() {
+            return true;
+        }
+        let point: Point = Point::new(x, y);
+        self.included_areas
+            .iter()
+            .any(|included_area| included_area.contains_point(&point))
+    }
+    pub(crate) fn is_size_mismatch_area(&self, x: usize, y: usize) -> bool {
+        let point: Point = Point::new(x, y);
+        self.size_mismatch_areas
+            .iter()
+            .any(|size_mismatch_area| size_mismatch_area.contains_point(&point))
+    }
+    pub(crate) fn is_exposed_area(&self, x: usize, y: usize) -> bool {
+        let point: Point = Point::new(x, y);
+        self.exposed_areas
+            .iter()
+            .any(|exposed_area| exposed_area.contains_point(&point))
+    }
+    pub(crate) fn is_ignored(&self, x: usize, y: usize) -> bool {
+        !self.is_included(x, y)
+            || self.is_masked(x, y)
+            || self.is_color_keyed(x, y)
+            || (self.size_mismatch_policy == SizeMismatchPolicy::CompareOverlap
+                && self.is_size_mismatch_area(x, y))
+            || self.is_exposed_area(x, y)
+    }
+    pub(crate) fn unmasked_pixel_count(&self) -> usize {
+        let total_pixel_count: usize = self.image_width as usize * self.image_height as usize;
+        if self.mask.is_none()
+            && self.color_key.is_none()
+            && self.included_areas.is_empty()
+            && self.size_mismatch_areas.is_empty()
+            && self.exposed_areas.is_empty()
+        {
+            return total_pixel_count;
+        }
+        let mut ignored_pixel_count: usize = 0;
+        for y in 0..self.image_height as usize {
+            for x in 0..self.image_width as usize {
+                if self.is_ignored(x, y) {
+                    ignored_pixel_count += 1;
+                }
+            }
+        }
+        total_pixel_count - ignored_pixel_count
+    }
+}
+/// Options that control how `compare_images_with_options` compares two images.
+#[derive(Debug, Clone)]
+pub struct ImageComparisonOptions {
+    /// Maximum distance in pixels between two different pixels of the same region.
+    pub threshold: u32,
+    /// Rectangles smaller than this size (in pixels) are not reported.
+    pub minimal_rectangle_size: u32,
+    /// Regions with fewer different pixels than this are not reported.
+    pub minimal_region_pixel_count: usize,
+    /// Regions narrower than this (in pixels) are not reported.
+    pub minimal_region_width: u32,
+    /// Regions lower than this (in pixels) are not reported.
+    pub minimal_region_height: u32,
+    /// Regions whose different pixels cover less than this fraction (0 - 1) of their
+    /// bounding box are not reported, e.g. sparse noise.
+    pub minimal_region_density: f64,
+    /// Images are considered matching when the percentage of different pixels is not above this value.
+    pub allowing_percent_of_different_pixels: f64,
+    /// Image of the same size as the compared images, where nonzero pixels mark areas that
+    /// are ignored. Masks with transparent pixels mark the non-transparent pixels instead.
+    pub mask_image: Option<PathBuf>,
+    /// Shade the ignored areas of `mask_image` in the result image.
+    pub shade_masked_area: bool,
+    /// Pixels of the expected image with this color are ignored and hatched in the result image.
+    pub ignore_color: Option<Rgba<u8>>,
+    /// Maximum difference per channel for a pixel to be treated as `ignore_color`.
+    pub ignore_color_tolerance: u8,
+    /// When not empty, only pixels inside these areas are compared.
+    pub included_areas: Vec<Rectangle>,
+    /// Crop the result image to the bounding box of `included_areas`.
+    pub crop_to_included_areas: bool,
+    /// Margin in pixels kept around `included_areas` when cropping the result image.
+    pub crop_margin: u32,
+    /// How images with different dimensions are compared.
+    pub size_mismatch_policy: SizeMismatchPolicy,
+    /// Maximum difference per channel that is not reported when an image was resampled
+    /// with `SizeMismatchPolicy::Resample`, to absorb the resampling noise. Not used with
+    /// `pixel_comparator`.
+    pub resampling_tolerance: u8,
+    /// Maximum difference per channel for two pixels to be considered equal.
+    pub color_tolerance: u8,
+    /// How the difference between two pixels is measured.
+    pub color_difference: ColorDifference,
+    /// Decides whether two pixels differ instead of `color_difference`, `color_tolerance`,
+    /// `resampling_tolerance` and `delta_e_threshold`; `color_difference` still selects the
+    /// color delta of the region statistics.
+    pub pixel_comparator: Option<Arc<dyn PixelComparator>>,
+    /// Pixels whose ΔE is above this value are different, used when `color_difference` is
+    /// one of the CIELAB formulas.
+    pub delta_e_threshold: f64,
+    /// A pixel matches when a pixel of the other image within this many pixels (horizontally
+    /// and vertically) has a tolerably close color, checked in both directions.
+    pub neighborhood_radius: u32,
+    /// Search radius in pixels for a global translation of the actual image, 0 disables
+    /// the search. Translations are scored with the pixel comparison of the other options
+    /// and the radius is clamped to the image dimensions.
+    pub shift_search_radius: u32,
+    /// Severity scores below the first value are `Severity::Low`, below the second value
+    /// `Severity::Medium` and `Severity::High` otherwise.
+    pub severity_thresholds: (f64, f64),
+    /// Outline regions in the result image with a color for their severity.
+    pub severity_colors: bool,
+    /// Maximum number of reported regions. The most severe regions are kept and the
+    /// remaining ones are summarized in the result, which stays a mismatch even when all
+    /// regions are dropped.
+    pub max_regions: Option<usize>,
+    /// Merge overlapping or nearby rectangles. Disable to report one rectangle per region.
+    pub merge_rectangles: bool,
+    /// Rectangles whose edges are at most this many pixels apart are merged, 0 only merges
+    /// overlapping rectangles.
+    pub merge_distance: u32,
+    /// Groups the different pixels into regions instead of `threshold` and the merging of
+    /// rectangles; the region filters still apply, to the grouped regions.
+    pub region_grouping: Option<Arc<dyn RegionGrouping>>,
+    /// Trace the outline of the different pixels of each region as polygons, which are drawn
+    /// in the result image instead of the rectangles and listed in the HTML and Markdown
+    /// reports.
+    pub trace_contours: bool,
+    /// Operations applied in order to the different pixels before they are grouped into
+    /// regions, e.g. `Opening` to drop isolated noise pixels.
+    pub morphological_operations: Vec<MorphologicalOperation>,
+    /// Side length in pixels of the square kernel of `morphological_operations`. Even sizes
+    /// use the next odd size and 0 and 1 leave the different pixels unchanged. Pixels outside
+    /// the images count as equal pixels.
+    pub morphology_kernel_size: u32,
+    /// Divides the compared images into a grid of (columns, rows) cells and reports the
+    /// fraction of different pixels of each cell in `ImageComparisonResult::grid`.
+    pub grid_size: Option<(u32, u32)>,
+    /// Cells of `grid_size` whose fraction of different pixels (0 - 1) is above this value
+    /// are highlighted.
+    pub grid_cell_threshold: f64,
+}
+
+impl Default for ImageComparisonOptions {
+    fn default() -> Self {
+        Self {
+            threshold: 5,
+            minimal_rectangle_size: 1,
+            minimal_region_pixel_count: 0,
+            minimal_region_width: 0,
+            minimal_region_height: 0,
+            minimal_region_density: 0f64,
+            allowing_percent_of_different_pixels: 0f64,
+            mask_image: None,
+            shade_masked_area: false,
+            ignore_color: None,
+            ignore_color_tolerance: 0,
+            included_areas: Vec::new(),
+            crop_to_included_areas: false,
+            crop_margin: 0,
+            size_mismatch_policy: SizeMismatchPolicy::Reject,
+            resampling_tolerance: 16,
+            color_tolerance: 0,
+            color_difference: ColorDifference::Rgb,
+            pixel_comparator: None,
+            delta_e_threshold: 2.3,
+            neighborhood_radius: 0,
+            shift_search_radius: 0,
+            severity_thresholds: (10f64, 100f64),
+            severity_colors: false,
+            max_regions: None,
+            merge_rectangles: true,
+            merge_distance: 0,
+            region_grouping: None,
+            trace_contours: false,
+            morphological_operations: Vec::new(),
+            morphology_kernel_size: 3,
+            grid_size: None,
+            grid_cell_threshold: 0f64,
+        }
+    }
+}
+/// Color-difference formula used to decide whether two pixels differ.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ColorDifference {
+    /// Per-channel RGBA comparison with `color_tolerance`.
+    Rgb,
+    /// CIE76: Euclidean distance in CIELAB.
+    DeltaE76,
+    /// CIE94 with graphic arts weighting.
+    DeltaE94,
+    /// CIEDE2000.
+    Ciede2000,
+}
+/// How `ImageComparisonResult::render` draws the differences.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RenderStyle {
+    /// The actual image with the regions outlined and the ignored areas marked.
+    Outline,
+    /// The actual image in dimmed gray with the different pixels colored from yellow to red
+    /// by their color delta.
+    HeatMap,
+    /// The expected image, the outline and the heat map side by side.
+    Composite,
+    /// The actual image with the grid of `grid_size` drawn and its highlighted cells shaded
+    /// in red, or the outline without `grid_size`.
+    Grid,
+}
+/// Morphological operation on the different pixels with a square kernel.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MorphologicalOperation {
+    /// Keeps pixels whose whole kernel is different, shrinking regions.
+    Erosion,
+    /// Marks pixels with any different pixel in their kernel, growing regions.
+    Dilation,
+    /// Erosion followed by dilation, removes regions smaller than the kernel.
+    Opening,
+    /// Dilation followed by erosion, fills gaps smaller than the kernel.
+    Closing,
+}
+/// Mean and maximum ΔE over a set of pixels.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DeltaEStatistics {
+    pub mean: f64,
+    pub max: f64,
+}
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+}
+/// Statistics of a reported difference region.
+///
+/// The color delta is the ΔE for the CIELAB color differences and the largest channel
+/// difference (0 - 255) for `ColorDifference::Rgb`.
+#[derive(Debug, Clone)]
+pub struct Region {
+    pub rectangle: Rectangle,
+    /// Outlines of the different pixels inside `rectangle` with `trace_contours`, as polygon
+    /// vertices in pixel corner coordinates: a single pixel at (x, y) has the vertices
+    /// (x, y), (x + 1, y), (x + 1, y + 1) and (x, y + 1).
+    pub contours: Vec<Vec<Point>>,
+    /// Number of different pixels inside `rectangle`.
+    pub pixel_count: usize,
+    pub centroid: (f64, f64),
+    pub mean_color_delta: f64,
+    pub max_color_delta: f64,
+    /// Number of different pixels weighted by their color delta relative to the largest
+    /// possible color delta.
+    pub severity_score: f64,
+    pub severity: Severity,
+}
+/// Fractions of different pixels in the cells of a grid over the compared images.
+#[derive(Debug, Clone)]
+pub struct DifferenceGrid {
+    pub columns: u32,
+    pub rows: u32,
+    /// Cells row by row, from the top-left to the bottom-right cell.
+    pub cells: Vec<GridCell>,
+}
+
+impl DifferenceGrid {
+    pub fn cell(&self, column: u32, row: u32) -> Option<&GridCell> {
+        if column >= self.columns || row >= self.rows {
+            return None;
+        }
+        self.cells.get((row * self.columns + column) as usize)
+    }
+    /// Fractions of different pixels as a matrix indexed by row, then by column.
+    pub fn fractions(&self) -> Vec<Vec<f64>> {
+        self.cells
+            .chunks(self.columns as usize)
+            .map(|row| row.iter().map(|cell| cell.difference_fraction).collect())
+            .collect()
+    }
+    pub fn highlighted_cells(&self) -> impl Iterator<Item = &GridCell> + '_ {
+        self.cells.iter().filter(|cell| cell.is_highlighted)
+    }
+}
+/// A cell of a `DifferenceGrid`.
+#[derive(Debug, Copy, Clone)]
+pub struct GridCell {
+    pub column: u32,
+    pub row: u32,
+    pub rectangle: Rectangle,
+    /// Different pixels divided by the pixels of the cell (0 - 1).
+    pub difference_fraction: f64,
+    /// Whether `difference_fraction` is above `grid_cell_threshold`.
+    pub is_highlighted: bool,
+}
+/// Policy applied when the expected and actual images have different dimensions.
+///
+/// With `CompareOverlap` and `Pad`, the rows and columns that exist in only one of the
+/// images are reported as a difference region.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SizeMismatchPolicy {
+    /// Stop with `ImageComparisonState::SizeMismatch`.
+    Reject,
+    /// Compare only the overlapping top-left area.
+    CompareOverlap,
+    /// Pad the smaller image with the given color up to the larger dimensions.
+    Pad(Rgba<u8>),
+    /// Scale the actual image to the dimensions of the expected image. No region is reported
+    /// for the size difference itself, see `ImageComparisonResult::is_size_mismatch`.
+    Scale,
+    /// Resample the higher-resolution image down to the dimensions of the lower-resolution
+    /// image with the given filter, e.g. for captures taken at different device pixel ratios.
+    Resample(FilterType),
 }
 #[derive(Debug, PartialEq, Eq)]
 pub enum ImageComparisonState {
@@ -46,12 +567,72 @@ pub enum ImageComparisonState {
 #[derive(Debug)]
 pub struct ImageComparisonResult {
     pub image_comparison_state: ImageComparisonState,
-    pub result_image: Option<RgbaImage>,
+    /// Percentage of different pixels, computed over the pixels that are not masked.
+    pub difference_percent: f64,
+    /// Dimensions (width, height) of the expected image as decoded, before any
+    /// `SizeMismatchPolicy` was applied.
+    pub expected_dimensions: (u32, u32),
+    /// Dimensions (width, height) of the actual image as decoded, before any
+    /// `SizeMismatchPolicy` was applied.
+    pub actual_dimensions: (u32, u32),
+    /// Whether the images have different dimensions. With `SizeMismatchPolicy::Scale` and
+    /// `SizeMismatchPolicy::Resample` the resized images can still match.
+    pub is_size_mismatch: bool,
+    /// Difference regions in the coordinates of the compared images.
+    pub rectangles: Vec<Rectangle>,
+    /// Difference regions in the coordinates of the higher-resolution image, when one of the
+    /// images was resampled with `SizeMismatchPolicy::Resample`.
+    pub high_resolution_rectangles: Vec<Rectangle>,
+    /// Translation (dx, dy) of the actual image relative to the expected image, found with
+    /// `shift_search_radius`.
+    pub detected_offset: (i32, i32),
+    /// Areas of the actual image that are only exposed by `detected_offset` and were not compared.
+    pub exposed_rectangles: Vec<Rectangle>,
+    /// ΔE over all compared pixels, when `color_difference` is one of the CIELAB formulas.
+    pub delta_e: Option<DeltaEStatistics>,
+    /// ΔE over the different pixels of each of `rectangles`, in the same order, when
+    /// `color_difference` is one of the CIELAB formulas.
+    pub region_delta_e: Vec<DeltaEStatistics>,
+    /// Statistics of each of `rectangles`, in the same order, from the most to the least severe.
+    pub regions: Vec<Region>,
+    /// Whether regions were dropped because of `max_regions`.
+    pub is_truncated: bool,
+    pub truncated_region_count: usize,
+    /// Rectangle enclosing all dropped regions.
+    pub truncated_area: Option<Rectangle>,
+    /// Fractions of different pixels per cell with `grid_size`.
+    pub grid: Option<DifferenceGrid>,
+    // kept for rendering on a mismatch
+    pub(crate) render_data: Option<RenderData>,
+}
+
+impl ImageComparisonResult {
+    pub(crate) fn create_with_state(image_comparison_state: ImageComparisonState) -> Self {
+        Self {
+            image_comparison_state,
+            difference_percent: 0f64,
+            expected_dimensions: (0, 0),
+            actual_dimensions: (0, 0),
+            is_size_mismatch: false,
+            rectangles: Vec::new(),
+            high_resolution_rectangles: Vec::new(),
+            detected_offset: (0, 0),
+            exposed_rectangles: Vec::new(),
+            delta_e: None,
+            region_delta_e: Vec::new(),
+            regions: Vec::new(),
+            is_truncated: false,
+            truncated_region_count: 0,
+            truncated_area: None,
+            grid: None,
+            render_data: None,
+        }
+    }
 }
 #[derive(Debug, Copy, Clone)]
-pub(crate) struct Rectangle {
-    pub(crate) min_point: Point,
-    pub(crate) max_point: Point,
+pub struct Rectangle {
+    pub min_point: Point,
+    pub max_point: Point,
 }
 
 impl Rectangle {
@@ -67,24 +648,19 @@ impl Rectangle {
             max_point: Point::new(0, 0),
         }
     }
-    pub(crate) fn create_with_points(min_point: Point, max_point: Point) -> Self {
+    pub fn create_with_points(min_point: Point, max_point: Point) -> Self {
         Self {
             min_point,
             max_point,
         }
     }
-    pub(crate) fn create_with_coordinates(
-        min_x: usize,
-        min_y: usize,
-        max_x: usize,
-        max_y: usize,
-    ) -> Self {
+    pub fn create_with_coordinates(min_x: usize, min_y: usize, max_x: usize, max_y: usize) -> Self {
         Self {
             min_point: Point::new(min_x, min_y),
             max_point: Point::new(max_x, max_y),
         }
     }
-    pub(crate) fn merge(&self, that: &Rectangle) -> Self {
+    pub fn merge(&self, that: &Rectangle) -> Self {
         Self::create_with_coordinates(
             min(self.min_point.x, that.min_point.x),
             min(self.min_point.y, that.min_point.y),
@@ -92,35 +668,44 @@ impl Rectangle {
             max(self.max_point.y, that.max_point.y),
         )
     }
-    pub(crate) fn is_overlapping(&self, that: &Rectangle) -> bool {
+    pub fn is_overlapping(&self, that: &Rectangle) -> bool {
         if self.max_point.y < that.min_point.y || that.max_point.y < self.min_point.y {
             return false;
         }
         self.max_point.x >= that.min_point.x && that.max_point.x >= self.min_point.x
     }
-    pub(crate) fn get_width(&self) -> usize {
+    pub fn is_within_distance(&self, that: &Rectangle, distance: usize) -> bool {
+        if self.max_point.y.saturating_add(distance) < that.min_point.y
+            || that.max_point.y.saturating_add(distance) < self.min_point.y
+        {
+            return false;
+        }
+        self.max_point.x.saturating_add(distance) >= that.min_point.x
+            && that.max_point.x.saturating_add(distance) >= self.min_point.x
+    }
+    pub fn get_width(&self) -> usize {
         self.max_point.x - self.min_point.x + 1
     }
-    pub(crate) fn get_height(&self) -> usize {
+    pub fn get_height(&self) -> usize {
         self.max_point.y - self.min_point.y + 1
     }
-    pub(crate) fn size(&self) -> usize {
+    pub fn size(&self) -> usize {
         self.get_width() * self.get_height()
     }
-    pub(crate) fn contains_point(&self, point: &Point) -> bool {
+    pub fn contains_point(&self, point: &Point) -> bool {
         point.x >= self.min_point.x
             && point.x <= self.max_point.x
             && point.y >= self.min_point.y
             && point.y <= self.max_point.y
     }
-    pub(crate) fn equals(&self, that: &Rectangle) -> bool {
+    pub fn equals(&self, that: &Rectangle) -> bool {
         self.min_point.equals(&that.min_point) && self.max_point.equals(&that.max_point)
     }
-    pub(crate) fn out_of_bounds(&self, image_comparison: &ImageComparison) -> bool {
-        if self.min_point.x >= image_comparison.image_width as usize
-            || self.max_point.x >= image_comparison.image_width as usize
-            || self.min_point.y >= image_comparison.image_height as usize
-            || self.max_point.y >= image_comparison.image_height as usize
+    pub(crate) fn out_of_bounds(&self, width: u32, height: u32) -> bool {
+        if self.min_point.x >= width as usize
+            || self.max_point.x >= width as usize
+            || self.min_point.y >= height as usize
+            || self.max_point.y >= height as usize
         {
             return true;
         }
@@ -128,17 +713,17 @@ impl Rectangle {
     }
 }
 #[derive(Debug, Copy, Clone)]
-pub(crate) struct Point {
-    pub(crate) x: usize,
-    pub(crate) y: usize,
+pub struct Point {
+    pub x: usize,
+    pub y: usize,
 }
 
 impl Point {
-    pub(crate) fn new(x: usize, y: usize) -> Self {
+    pub fn new(x: usize, y: usize) -> Self {
         Self { x, y }
     }
-    pub(crate) fn equals(&self, that: &Point) -> bool {
-        return self.x == that.x && self.y == that.y;
+    pub fn equals(&self, that: &Point) -> bool {
+        self.x == that.x && self.y == that.y
     }
     pub(crate) fn increment(&mut self) {
         if let (Some(result_x), Some(result_y)) = (self.x.checked_add(1), self.y.checked_add(1)) {
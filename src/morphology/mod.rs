@@ -0,0 +1,173 @@
+use crate::bitmatrix::BitMatrix;
+use crate::model::MorphologicalOperation;
+use std::cmp::min;
+
+// the kernel is a square of 2 * (kernel_size / 2) + 1 pixels centered on each pixel, so even
+// sizes use the next odd size and sizes 0 and 1 leave the matrix unchanged; the matrix is
+// padded with unset pixels, which lets the dilation grow past the border and makes the
+// erosion shrink pixels at the border, the same for all operations
+pub(crate) fn apply(matrix: &mut BitMatrix, operation: MorphologicalOperation, kernel_size: usize) {
+    let radius: usize = kernel_size / 2;
+    if radius == 0 {
+        return;
+    }
+    let mut padded: BitMatrix =
+        BitMatrix::new(matrix.width() + 2 * radius, matrix.height() + 2 * radius);
+    for (x, y) in matrix.iter_ones() {
+        padded.set(x + radius, y + radius, true);
+    }
+    match operation {
+        MorphologicalOperation::Erosion => erode(&mut padded, radius),
+        MorphologicalOperation::Dilation => dilate(&mut padded, radius),
+        MorphologicalOperation::Opening => {
+            erode(&mut padded, radius);
+            dilate(&mut padded, radius);
+        }
+        MorphologicalOperation::Closing => {
+            dilate(&mut padded, radius);
+            erode(&mut padded, radius);
+        }
+    }
+    for y in 0..matrix.height() {
+        for x in 0..matrix.width() {
+            matrix.set(x, y, padded.get(x + radius, y + radius));
+        }
+    }
+}
+
+fn erode(matrix: &mut BitMatrix, radius: usize) {
+    // a pixel stays set when all pixels of its window are set
+    filter(matrix, radius, |count, window| count == window);
+}
+
+fn dilate(matrix: &mut BitMatrix, radius: usize) {
+    // a pixel is set when any pixel of its window is set
+    filter(matrix, radius, |count, _| count > 0);
+}
+
+// the square kernel is separable: filter the rows, then the columns of the result
+fn filter<F>(matrix: &mut BitMatrix, radius: usize, is_set: F)
+where
+    F: Fn(usize, usize) -> bool,
+{
+    for y in 0..matrix.height() {
+        let values: Vec<bool> = (0..matrix.width()).map(|x| matrix.get(x, y)).collect();
+        for (x, value) in filter_line(&values, radius, &is_set)
+            .into_iter()
+            .enumerate()
+        {
+            matrix.set(x, y, value);
+        }
+    }
+    for x in 0..matrix.width() {
+        let values: Vec<bool> = (0..matrix.height()).map(|y| matrix.get(x, y)).collect();
+        for (y, value) in filter_line(&values, radius, &is_set)
+            .into_iter()
+            .enumerate()
+        {
+            matrix.set(x, y, value);
+        }
+    }
+}
+
+fn filter_line<F>(values: &[bool], radius: usize, is_set: &F) -> Vec<bool>
+where
+    F: Fn(usize, usize) -> bool,
+{
+    let mut prefix_sums: Vec<usize> = vec![0; values.len() + 1];
+    for (index, value) in values.iter().enumerate() {
+        prefix_sums[index + 1] = prefix_sums[index] + usize::from(*value);
+    }
+    (0..values.len())
+        .map(|index| {
+            let start: usize = index.saturating_sub(radius);
+            let end: usize = min(index + radius + 1, values.len());
+            let count: usize = prefix_sums[end] - prefix_sums[start];
+            is_set(count, end - start)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::apply;
+    use crate::bitmatrix::BitMatrix;
+    use crate::model::MorphologicalOperation;
+    use ndarray::{arr2, Array2};
+    fn bit_matrix(values: Array2<u8>) -> BitMatrix {
+        let mut matrix: BitMatrix = BitMatrix::new(values.ncols(), values.nrows());
+        for ((y, x), value) in values.indexed_iter() {
+            matrix.set(x, y, *value == 1);
+        }
+        matrix
+    }
+    fn noisy_block() -> BitMatrix {
+        bit_matrix(arr2(&[
+            [1, 0, 0, 0, 0, 0],
+            [0, 0, 1, 1, 1, 0],
+            [0, 0, 1, 1, 1, 0],
+            [0, 0, 1, 1, 1, 0],
+            [0, 0, 0, 0, 0, 0],
+            [0, 0, 0, 0, 0, 1],
+        ]))
+    }
+    #[test]
+    fn erosion_keeps_block_center() {
+        let mut matrix: BitMatrix = noisy_block();
+        apply(&mut matrix, MorphologicalOperation::Erosion, 3);
+        assert_eq!(1, matrix.count_ones());
+        assert!(matrix.get(3, 2));
+    }
+    #[test]
+    fn dilation_grows_pixels() {
+        let mut matrix: BitMatrix = bit_matrix(arr2(&[[0, 0, 0], [0, 1, 0], [0, 0, 0]]));
+        apply(&mut matrix, MorphologicalOperation::Dilation, 3);
+        assert_eq!(9, matrix.count_ones());
+    }
+    #[test]
+    fn opening_removes_noise() {
+        let mut matrix: BitMatrix = noisy_block();
+        apply(&mut matrix, MorphologicalOperation::Opening, 3);
+        assert_eq!(9, matrix.count_ones());
+        assert!(!matrix.get(0, 0));
+        assert!(!matrix.get(5, 5));
+    }
+    #[test]
+    fn closing_fills_gaps() {
+        let mut matrix: BitMatrix = bit_matrix(arr2(&[
+            [0, 0, 0, 0, 0],
+            [0, 1, 0, 1, 0],
+            [0, 1, 0, 1, 0],
+            [0, 1, 0, 1, 0],
+            [0, 0, 0, 0, 0],
+        ]));
+        apply(&mut matrix, MorphologicalOperation::Closing, 3);
+        assert!(matrix.get(2, 2));
+        assert!(!matrix.get(0, 2));
+        assert!(!matrix.get(0, 0));
+        assert_eq!(9, matrix.count_ones());
+    }
+    #[test]
+    fn erosion_shrinks_border_pixels() {
+        let mut matrix: BitMatrix = bit_matrix(arr2(&[[1, 1, 1], [1, 1, 1], [1, 1, 1]]));
+        apply(&mut matrix, MorphologicalOperation::Erosion, 3);
+        assert_eq!(1, matrix.count_ones());
+        assert!(matrix.get(1, 1));
+    }
+    #[test]
+    fn even_kernel_size_uses_next_odd_size() {
+        let mut even: BitMatrix = noisy_block();
+        apply(&mut even, MorphologicalOperation::Opening, 2);
+        let mut odd: BitMatrix = noisy_block();
+        apply(&mut odd, MorphologicalOperation::Opening, 3);
+        assert_eq!(odd, even);
+    }
+    #[test]
+    fn kernel_size_one_is_identity() {
+        let mut matrix: BitMatrix = noisy_block();
+        apply(&mut matrix, MorphologicalOperation::Opening, 1);
+        assert_eq!(noisy_block(), matrix);
+        apply(&mut matrix, MorphologicalOperation::Closing, 0);
+        assert_eq!(noisy_block(), matrix);
+    }
+}
@@ -0,0 +1,217 @@
+use std::fmt;
+
+use crate::color::{self, Lab};
+use crate::model::{ColorDifference, ImageComparisonOptions};
+use image::Rgba;
+use std::sync::Arc;
+
+/// Decides whether two pixels at the same position differ.
+///
+/// Closures taking the expected and the actual pixel implement this trait too.
+pub trait PixelComparator: Send + Sync {
+    fn differs(&self, expected: &Rgba<u8>, actual: &Rgba<u8>) -> bool;
+}
+
+impl<F> PixelComparator for F
+where
+    F: Fn(&Rgba<u8>, &Rgba<u8>) -> bool + Send + Sync,
+{
+    fn differs(&self, expected: &Rgba<u8>, actual: &Rgba<u8>) -> bool {
+        self(expected, actual)
+    }
+}
+
+impl fmt::Debug for dyn PixelComparator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PixelComparator")
+    }
+}
+
+/// Pixels differ when any channel differs.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ExactPixelComparator;
+
+impl PixelComparator for ExactPixelComparator {
+    fn differs(&self, expected: &Rgba<u8>, actual: &Rgba<u8>) -> bool {
+        expected.ne(actual)
+    }
+}
+
+/// Pixels differ when any channel differs by more than `tolerance`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TolerancePixelComparator {
+    pub tolerance: u8,
+}
+
+impl PixelComparator for TolerancePixelComparator {
+    fn differs(&self, expected: &Rgba<u8>, actual: &Rgba<u8>) -> bool {
+        expected
+            .0
+            .iter()
+            .zip(actual.0.iter())
+            .any(|(e, a)| e.abs_diff(*a) > self.tolerance)
+    }
+}
+
+/// Pixels differ when the Euclidean distance of their RGBA channels (0 - 510) is above
+/// `threshold`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct EuclideanPixelComparator {
+    pub threshold: f64,
+}
+
+impl PixelComparator for EuclideanPixelComparator {
+    fn differs(&self, expected: &Rgba<u8>, actual: &Rgba<u8>) -> bool {
+        let distance_squared: f64 = expected
+            .0
+            .iter()
+            .zip(actual.0.iter())
+            .map(|(e, a)| (*e as f64 - *a as f64).powi(2))
+            .sum();
+        distance_squared.sqrt() > self.threshold
+    }
+}
+
+/// Pixels differ when their ΔE is above `threshold` or their alpha channels differ by more
+/// than `alpha_tolerance`. `ColorDifference::Rgb` is not a ΔE formula and measures the ΔE
+/// like `ColorDifference::DeltaE76`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DeltaEPixelComparator {
+    pub color_difference: ColorDifference,
+    pub threshold: f64,
+    pub alpha_tolerance: u8,
+}
+
+impl PixelComparator for DeltaEPixelComparator {
+    fn differs(&self, expected: &Rgba<u8>, actual: &Rgba<u8>) -> bool {
+        expected.0[3].abs_diff(actual.0[3]) > self.alpha_tolerance
+            || color::delta_e(self.color_difference, &expected.0, &actual.0) > self.threshold
+    }
+}
+
+/// Pixels differ when their CIELAB lightness (0 - 100) differs by more than `tolerance`,
+/// ignoring hue and saturation.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LuminancePixelComparator {
+    pub tolerance: f64,
+}
+
+impl PixelComparator for LuminancePixelComparator {
+    fn differs(&self, expected: &Rgba<u8>, actual: &Rgba<u8>) -> bool {
+        (Lab::from_srgb(&expected.0).l - Lab::from_srgb(&actual.0).l).abs() > self.tolerance
+    }
+}
+
+// the single place that decides whether two pixels differ: with the pixel comparator when
+// one is set, otherwise with the color difference, the tolerance and the ΔE threshold
+#[derive(Debug, Clone)]
+pub(crate) struct PixelComparison {
+    pixel_comparator: Option<Arc<dyn PixelComparator>>,
+    color_difference: ColorDifference,
+    tolerance: u8,
+    delta_e_threshold: f64,
+}
+
+impl PixelComparison {
+    // tolerance replaces color_tolerance, e.g. with the resampling tolerance
+    pub(crate) fn create_with_options(options: &ImageComparisonOptions, tolerance: u8) -> Self {
+        Self {
+            pixel_comparator: options.pixel_comparator.clone(),
+            color_difference: options.color_difference,
+            tolerance,
+            delta_e_threshold: options.delta_e_threshold,
+        }
+    }
+    pub(crate) fn differs(&self, e: &[u8; 4], a: &[u8; 4]) -> bool {
+        self.differs_with_delta_e(e, a, None)
+    }
+    // delta_e is the ΔE of the pixels when it was already computed, e.g. for the statistics;
+    // tolerance applies per channel with ColorDifference::Rgb and to the alpha channel
+    // otherwise
+    pub(crate) fn differs_with_delta_e(
+        &self,
+        e: &[u8; 4],
+        a: &[u8; 4],
+        delta_e: Option<f64>,
+    ) -> bool {
+        if let Some(pixel_comparator) = &self.pixel_comparator {
+            return pixel_comparator.differs(&Rgba::<u8>(*e), &Rgba::<u8>(*a));
+        }
+        match self.color_difference {
+            ColorDifference::Rgb => {
+                if self.tolerance == 0 {
+                    return e.ne(a);
+                }
+                e.iter()
+                    .zip(a.iter())
+                    .any(|(e, a)| e.abs_diff(*a) > self.tolerance)
+            }
+            color_difference => {
+                let delta_e: f64 =
+                    delta_e.unwrap_or_else(|| color::delta_e(color_difference, e, a));
+                e[3].abs_diff(a[3]) > self.tolerance || delta_e > self.delta_e_threshold
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        DeltaEPixelComparator, EuclideanPixelComparator, ExactPixelComparator,
+        LuminancePixelComparator, PixelComparator, PixelComparison, TolerancePixelComparator,
+    };
+    use crate::model::{ColorDifference, ImageComparisonOptions};
+    use image::Rgba;
+    use std::sync::Arc;
+    #[test]
+    fn built_in_pixel_comparators() {
+        let expected: Rgba<u8> = Rgba::<u8>([100, 100, 100, 255]);
+        let actual: Rgba<u8> = Rgba::<u8>([103, 104, 100, 255]);
+        assert!(!ExactPixelComparator.differs(&expected, &expected));
+        assert!(ExactPixelComparator.differs(&expected, &actual));
+        assert!(TolerancePixelComparator { tolerance: 3 }.differs(&expected, &actual));
+        assert!(!TolerancePixelComparator { tolerance: 4 }.differs(&expected, &actual));
+        assert!(!EuclideanPixelComparator { threshold: 5f64 }.differs(&expected, &actual));
+        assert!(EuclideanPixelComparator { threshold: 4.9 }.differs(&expected, &actual));
+        let delta_e: DeltaEPixelComparator = DeltaEPixelComparator {
+            color_difference: ColorDifference::Ciede2000,
+            threshold: 2.3,
+            alpha_tolerance: 0,
+        };
+        assert!(!delta_e.differs(&expected, &Rgba::<u8>([101, 101, 100, 255])));
+        assert!(delta_e.differs(&expected, &Rgba::<u8>([110, 100, 100, 255])));
+        assert!(delta_e.differs(&expected, &Rgba::<u8>([100, 100, 100, 254])));
+    }
+    #[test]
+    fn luminance_pixel_comparator_ignores_hue() {
+        // pure red and a gray of nearly the same lightness
+        let red: Rgba<u8> = Rgba::<u8>([255, 0, 0, 255]);
+        let gray: Rgba<u8> = Rgba::<u8>([127, 127, 127, 255]);
+        let luminance: LuminancePixelComparator = LuminancePixelComparator { tolerance: 1f64 };
+        assert!(!luminance.differs(&red, &gray));
+        assert!(luminance.differs(&red, &Rgba::<u8>([0, 0, 0, 255])));
+    }
+    #[test]
+    fn closure_pixel_comparator() {
+        let red_only = |expected: &Rgba<u8>, actual: &Rgba<u8>| expected.0[0] != actual.0[0];
+        assert!(!red_only.differs(&Rgba::<u8>([1, 2, 3, 255]), &Rgba::<u8>([1, 0, 0, 255])));
+        assert!(red_only.differs(&Rgba::<u8>([1, 2, 3, 255]), &Rgba::<u8>([0, 2, 3, 255])));
+    }
+    #[test]
+    fn pixel_comparison_dispatches_once() {
+        let mut options: ImageComparisonOptions = ImageComparisonOptions::default();
+        let rgb: PixelComparison = PixelComparison::create_with_options(&options, 3);
+        assert!(!rgb.differs(&[100, 100, 100, 255], &[103, 97, 100, 255]));
+        assert!(rgb.differs(&[100, 100, 100, 255], &[104, 100, 100, 255]));
+        options.color_difference = ColorDifference::Ciede2000;
+        options.delta_e_threshold = 2.3;
+        let delta_e: PixelComparison = PixelComparison::create_with_options(&options, 0);
+        assert!(!delta_e.differs(&[100, 100, 100, 255], &[101, 101, 100, 255]));
+        assert!(delta_e.differs(&[100, 100, 100, 255], &[100, 100, 100, 254]));
+        assert!(delta_e.differs_with_delta_e(&[0, 0, 0, 255], &[0, 0, 0, 255], Some(3f64)));
+        options.pixel_comparator = Some(Arc::new(ExactPixelComparator));
+        let comparator: PixelComparison = PixelComparison::create_with_options(&options, 255);
+        assert!(comparator.differs(&[100, 100, 100, 255], &[101, 100, 100, 255]));
+    }
+}
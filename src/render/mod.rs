@@ -0,0 +1,358 @@
+use crate::color;
+use crate::model::{
+    DifferenceGrid, ImageComparisonResult, Point, Rectangle, Region, RenderData, RenderStyle,
+    Severity,
+};
+use image::imageops;
+use image::{Rgba, RgbaImage};
+use std::cmp::min;
+
+impl ImageComparisonResult {
+    /// Draws the differences found by the comparison, which can be called repeatedly with
+    /// different styles. Returns `None` unless the state is `ImageComparisonState::Mismatch`.
+    pub fn render(&self, style: RenderStyle) -> Option<RgbaImage> {
+        self.render_data
+            .as_ref()
+            .map(|render_data| render_result_image(render_data, &self.regions, style))
+    }
+}
+
+fn render_result_image(
+    render_data: &RenderData,
+    regions: &[Region],
+    style: RenderStyle,
+) -> RgbaImage {
+    match style {
+        RenderStyle::Outline => crop_result_image(
+            render_data,
+            draw_result_image(render_data, regions, Rgba::<u8>([255, 0, 0, 255])),
+        ),
+        RenderStyle::HeatMap => crop_result_image(render_data, draw_heat_map(render_data)),
+        RenderStyle::Grid => match &render_data.grid {
+            Some(grid) => crop_result_image(render_data, draw_grid(render_data, grid)),
+            None => render_result_image(render_data, regions, RenderStyle::Outline),
+        },
+        RenderStyle::Composite => {
+            let panels: [RgbaImage; 3] = [
+                crop_result_image(render_data, render_data.expected.to_rgba8()),
+                render_result_image(render_data, regions, RenderStyle::Outline),
+                render_result_image(render_data, regions, RenderStyle::HeatMap),
+            ];
+            let (width, height) = panels[0].dimensions();
+            let mut result: RgbaImage = RgbaImage::new(width * 3, height);
+            for (index, panel) in panels.iter().enumerate() {
+                imageops::replace(&mut result, panel, (width * index as u32) as i64, 0);
+            }
+            result
+        }
+    }
+}
+
+fn crop_result_image(render_data: &RenderData, image: RgbaImage) -> RgbaImage {
+    if render_data.crop_to_included_areas && !render_data.included_areas.is_empty() {
+        return crop_to_included_areas(render_data, &image);
+    }
+    image
+}
+
+fn draw_heat_map(render_data: &RenderData) -> RgbaImage {
+    let mut result: RgbaImage = render_data.actual.to_rgba8();
+    for (x, y, pixel) in result.enumerate_pixels_mut() {
+        let (x, y) = (x as usize, y as usize);
+        // pixels that exist in only one of the images differ completely
+        let heat: Option<f64> = if render_data.is_size_mismatch_area(x, y) {
+            Some(1f64)
+        } else if render_data.is_different(x, y) {
+            Some(
+                color_delta(render_data, x, y)
+                    / color::max_color_delta(render_data.color_difference),
+            )
+        } else {
+            None
+        };
+        *pixel = match heat {
+            Some(heat) => {
+                let green: u8 = (255f64 * (1f64 - heat.clamp(0f64, 1f64))).round() as u8;
+                Rgba::<u8>([255, green, 0, 255])
+            }
+            None => {
+                let luminance: f64 = 0.299 * pixel.0[0] as f64
+                    + 0.587 * pixel.0[1] as f64
+                    + 0.114 * pixel.0[2] as f64;
+                let gray: u8 = (luminance / 2f64).round() as u8;
+                Rgba::<u8>([gray, gray, gray, 255])
+            }
+        };
+    }
+    result
+}
+
+fn color_delta(render_data: &RenderData, x: usize, y: usize) -> f64 {
+    let e: &Rgba<u8> = render_data
+        .expected
+        .as_rgba8()
+        .expect("msg")
+        .get_pixel(x as u32, y as u32);
+    let a: &Rgba<u8> = render_data
+        .actual
+        .as_rgba8()
+        .expect("msg")
+        .get_pixel(x as u32, y as u32);
+    color::color_delta(render_data.color_difference, &e.0, &a.0)
+}
+
+fn draw_grid(render_data: &RenderData, grid: &DifferenceGrid) -> RgbaImage {
+    let mut result: RgbaImage = render_data.actual.to_rgba8();
+    for cell in grid.highlighted_cells() {
+        let rectangle: &Rectangle = &cell.rectangle;
+        for y in rectangle.min_point.y..=rectangle.max_point.y {
+            for x in rectangle.min_point.x..=rectangle.max_point.x {
+                blend_pixel(
+                    result.get_pixel_mut(x as u32, y as u32),
+                    Rgba::<u8>([255, 0, 0, 255]),
+                );
+            }
+        }
+    }
+    for cell in grid.cells.iter() {
+        draw_rectangle(
+            &mut result,
+            &cell.rectangle,
+            Rgba::<u8>([128, 128, 128, 255]),
+        );
+    }
+    for cell in grid.highlighted_cells() {
+        draw_rectangle(&mut result, &cell.rectangle, Rgba::<u8>([255, 0, 0, 255]));
+    }
+    result
+}
+
+fn draw_result_image(render_data: &RenderData, regions: &[Region], color: Rgba<u8>) -> RgbaImage {
+    let mut result: RgbaImage = render_data.actual.to_rgba8();
+    if render_data.shade_masked_area {
+        shade_masked_area(render_data, &mut result, Rgba::<u8>([128, 128, 128, 255]));
+    }
+    if render_data.color_key.is_some() {
+        hatch_color_keyed_area(render_data, &mut result, Rgba::<u8>([64, 64, 64, 255]));
+    }
+    draw_rectangles(
+        render_data,
+        &mut result,
+        render_data.exposed_areas.clone(),
+        Rgba::<u8>([0, 0, 255, 255]),
+    );
+    if let Some(truncated_area) = render_data.truncated_area {
+        draw_rectangles(
+            render_data,
+            &mut result,
+            vec![truncated_area],
+            Rgba::<u8>([128, 128, 128, 255]),
+        );
+    }
+    for region in regions.iter() {
+        let region_color: Rgba<u8> = if render_data.severity_colors {
+            match region.severity {
+                Severity::Low => Rgba::<u8>([255, 215, 0, 255]),
+                Severity::Medium => Rgba::<u8>([255, 140, 0, 255]),
+                Severity::High => Rgba::<u8>([255, 0, 0, 255]),
+            }
+        } else {
+            color
+        };
+        if render_data.trace_contours && !region.contours.is_empty() {
+            for contour in region.contours.iter() {
+                draw_polygon(&mut result, contour, region_color);
+            }
+        } else {
+            draw_rectangles(
+                render_data,
+                &mut result,
+                vec![region.rectangle],
+                region_color,
+            );
+        }
+    }
+    result
+}
+
+fn crop_to_included_areas(render_data: &RenderData, image: &RgbaImage) -> RgbaImage {
+    let margin: usize = render_data.crop_margin as usize;
+    let mut bounding_box: Rectangle = render_data.included_areas[0];
+    for included_area in render_data.included_areas.iter() {
+        bounding_box = bounding_box.merge(included_area);
+    }
+    let min_x: usize = bounding_box.min_point.x.saturating_sub(margin);
+    let min_y: usize = bounding_box.min_point.y.saturating_sub(margin);
+    let max_x: usize = min(
+        bounding_box.max_point.x.saturating_add(margin),
+        render_data.image_width as usize - 1,
+    );
+    let max_y: usize = min(
+        bounding_box.max_point.y.saturating_add(margin),
+        render_data.image_height as usize - 1,
+    );
+    if min_x > max_x || min_y > max_y {
+        return image.clone();
+    }
+    imageops::crop_imm(
+        image,
+        min_x as u32,
+        min_y as u32,
+        (max_x - min_x + 1) as u32,
+        (max_y - min_y + 1) as u32,
+    )
+    .to_image()
+}
+
+fn shade_masked_area(render_data: &RenderData, image: &mut RgbaImage, color: Rgba<u8>) {
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        if render_data.is_masked(x as usize, y as usize) {
+            blend_pixel(pixel, color);
+        }
+    }
+}
+
+fn hatch_color_keyed_area(render_data: &RenderData, image: &mut RgbaImage, color: Rgba<u8>) {
+    let hatch_spacing: u32 = 6;
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        if render_data.is_color_keyed(x as usize, y as usize) {
+            if (x + y) % hatch_spacing == 0 {
+                *pixel = color;
+            } else {
+                blend_pixel(pixel, Rgba::<u8>([255, 255, 255, 255]));
+            }
+        }
+    }
+}
+
+fn blend_pixel(pixel: &mut Rgba<u8>, color: Rgba<u8>) {
+    for channel in 0..3 {
+        pixel.0[channel] = ((pixel.0[channel] as u16 + color.0[channel] as u16) / 2) as u8;
+    }
+    pixel.0[3] = 255;
+}
+
+fn draw_rectangles(
+    render_data: &RenderData,
+    result: &mut RgbaImage,
+    rectangles: Vec<Rectangle>,
+    color: Rgba<u8>,
+) {
+    for rectangle in rectangles.iter() {
+        for outline_rectangle in
+            outline_rectangles(rectangle, render_data.image_width, render_data.image_height)
+        {
+            draw_rectangle(result, &outline_rectangle, color);
+        }
+    }
+}
+
+// the rectangle and the two rectangles around it that fit in the image, so outlines are up
+// to 3 pixels wide and grow outwards
+pub(crate) fn outline_rectangles(rectangle: &Rectangle, width: u32, height: u32) -> Vec<Rectangle> {
+    let thickness: u32 = 2;
+    let mut rectangle: Rectangle = *rectangle;
+    let mut outline_rectangles: Vec<Rectangle> = Vec::new();
+    for i in 0..=thickness {
+        if i > 0 {
+            rectangle.min_point.decrement();
+            rectangle.max_point.increment();
+        }
+        if !rectangle.out_of_bounds(width, height) {
+            outline_rectangles.push(rectangle);
+        }
+    }
+    outline_rectangles
+}
+
+fn draw_rectangle(image: &mut RgbaImage, rectangle: &Rectangle, color: Rgba<u8>) {
+    let min_point_x: u32 = rectangle.min_point.x.try_into().unwrap();
+    let min_point_y: u32 = rectangle.min_point.y.try_into().unwrap();
+    let max_point_x: u32 = rectangle.max_point.x.try_into().unwrap();
+    let max_point_y: u32 = rectangle.max_point.y.try_into().unwrap();
+    draw_line_segment(
+        image,
+        (min_point_x, min_point_y),
+        (max_point_x, min_point_y),
+        color,
+    );
+    draw_line_segment(
+        image,
+        (min_point_x, max_point_y),
+        (max_point_x, max_point_y),
+        color,
+    );
+    draw_line_segment(
+        image,
+        (min_point_x, min_point_y),
+        (min_point_x, max_point_y),
+        color,
+    );
+    draw_line_segment(
+        image,
+        (max_point_x, min_point_y),
+        (max_point_x, max_point_y),
+        color,
+    );
+}
+
+fn draw_line_segment(image: &mut RgbaImage, start: (u32, u32), end: (u32, u32), color: Rgba<u8>) {
+    // vertical line segment
+    if start.0 == end.0 {
+        for y in start.1..=end.1 {
+            image.put_pixel(start.0, y, color);
+        }
+    }
+    // horizontal line segment
+    if start.1 == end.1 {
+        for x in start.0..=end.0 {
+            image.put_pixel(x, start.1, color);
+        }
+    }
+}
+
+// contour vertices are pixel corners of a clockwise polygon, so the different pixels are on
+// the right-hand side of each edge; these pixels are drawn to keep the outline inside the region
+fn draw_polygon(image: &mut RgbaImage, vertices: &[Point], color: Rgba<u8>) {
+    for (index, vertex) in vertices.iter().enumerate() {
+        let next: &Point = &vertices[(index + 1) % vertices.len()];
+        let (x0, y0) = (vertex.x as u32, vertex.y as u32);
+        let (x1, y1) = (next.x as u32, next.y as u32);
+        if y0 == y1 && x0 < x1 {
+            // eastwards: the pixels below the edge
+            draw_line_segment(image, (x0, y0), (x1 - 1, y0), color);
+        } else if y0 == y1 && x1 < x0 {
+            // westwards: the pixels above the edge
+            draw_line_segment(image, (x1, y0 - 1), (x0 - 1, y0 - 1), color);
+        } else if x0 == x1 && y0 < y1 {
+            // southwards: the pixels left of the edge
+            draw_line_segment(image, (x0 - 1, y0), (x0 - 1, y1 - 1), color);
+        } else if x0 == x1 && y1 < y0 {
+            // northwards: the pixels right of the edge
+            draw_line_segment(image, (x0, y1), (x0, y0 - 1), color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::draw_polygon;
+    use crate::model::Point;
+    use image::{Rgba, RgbaImage};
+    #[test]
+    fn draw_polygon_inside_region() {
+        // the contour of the 2 x 2 pixels at (1, 1)
+        let vertices: Vec<Point> = vec![
+            Point::new(1, 1),
+            Point::new(3, 1),
+            Point::new(3, 3),
+            Point::new(1, 3),
+        ];
+        let mut image: RgbaImage = RgbaImage::new(4, 4);
+        draw_polygon(&mut image, &vertices, Rgba::<u8>([255, 0, 0, 255]));
+        for (x, y, pixel) in image.enumerate_pixels() {
+            let is_region: bool = (1..3).contains(&x) && (1..3).contains(&y);
+            assert_eq!(is_region, pixel.0[3] == 255, "pixel ({}, {})", x, y);
+        }
+    }
+}
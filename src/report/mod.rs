@@ -0,0 +1,789 @@
+use std::env;
+use std::ffi::OsString;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use crate::batch::{file_name, BatchResult, ComparisonPair};
+use crate::model::{Region, RenderStyle};
+use image::{DynamicImage, ImageFormat, RgbaImage};
+
+const IMAGES_DIRECTORY: &str = "images";
+const THUMBNAIL_SIZE: u32 = 160;
+const MARKDOWN_REGION_LIMIT: usize = 10;
+
+// paths of the images of a pair relative to the report, None when the image is not available
+struct ReportImages {
+    expected: String,
+    actual: String,
+    diff: Option<String>,
+    thumbnail: Option<String>,
+}
+
+/// Writes a static HTML report of the batch to `index.html` in `report_directory` and
+/// returns its path.
+///
+/// The page has a summary table with a thumbnail per pair and a detail view per pair with
+/// its regions and their contours, the expected, actual and diff images, an overlay slider
+/// and an onion-skin opacity control. The images are copied to the `images` subdirectory
+/// and linked relative to the report, so the directory can be moved or archived as a whole.
+pub fn write_html_report(batch: &BatchResult, report_directory: &Path) -> io::Result<PathBuf> {
+    let images_directory: PathBuf = report_directory.join(IMAGES_DIRECTORY);
+    fs::create_dir_all(&images_directory)?;
+    let mut report_images: Vec<ReportImages> = Vec::new();
+    for (index, pair) in batch.pairs.iter().enumerate() {
+        report_images.push(write_pair_images(pair, index, &images_directory)?);
+    }
+    let report_path: PathBuf = report_directory.join("index.html");
+    fs::write(&report_path, create_html(batch, &report_images))?;
+    Ok(report_path)
+}
+
+fn write_pair_images(
+    pair: &ComparisonPair,
+    index: usize,
+    images_directory: &Path,
+) -> io::Result<ReportImages> {
+    let expected: String = copy_image(&pair.expected_image, index, "expected", images_directory)?;
+    let actual: String = copy_image(&pair.actual_image, index, "actual", images_directory)?;
+    // a written diff image is used when the pair no longer has the images to render it
+    let diff_image: Option<RgbaImage> = match &pair.diff_image {
+        Some(diff_image) => Some(
+            image::open(diff_image)
+                .map_err(|error| io::Error::new(io::ErrorKind::Other, error))?
+                .to_rgba8(),
+        ),
+        None => pair.result.render(RenderStyle::Outline),
+    };
+    let diff: Option<String> = match &diff_image {
+        Some(diff_image) => {
+            let file_name: String = format!("{}-diff.png", index);
+            save_png(diff_image, &images_directory.join(&file_name))?;
+            Some(file_name)
+        }
+        None => None,
+    };
+    // the diff shows where a failing pair differs, passing pairs show the actual image
+    let thumbnail_source: Option<DynamicImage> = match diff_image {
+        Some(diff_image) => Some(DynamicImage::ImageRgba8(diff_image)),
+        None => image::open(&pair.actual_image).ok(),
+    };
+    let thumbnail: Option<String> = match thumbnail_source {
+        Some(thumbnail_source) => {
+            let file_name: String = format!("{}-thumbnail.png", index);
+            let thumbnail_image: RgbaImage = thumbnail_source
+                .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+                .to_rgba8();
+            save_png(&thumbnail_image, &images_directory.join(&file_name))?;
+            Some(file_name)
+        }
+        None => None,
+    };
+    Ok(ReportImages {
+        expected: relative_image_path(&expected),
+        actual: relative_image_path(&actual),
+        diff: diff.as_deref().map(relative_image_path),
+        thumbnail: thumbnail.as_deref().map(relative_image_path),
+    })
+}
+
+// images are renamed after the pair index, so the links need no escaping
+fn copy_image(
+    image: &Path,
+    index: usize,
+    role: &str,
+    images_directory: &Path,
+) -> io::Result<String> {
+    let file_name: String = match image.extension() {
+        Some(extension) => format!("{}-{}.{}", index, role, extension.to_string_lossy()),
+        None => format!("{}-{}", index, role),
+    };
+    fs::copy(image, images_directory.join(&file_name))?;
+    Ok(file_name)
+}
+
+fn save_png(image: &RgbaImage, path: &Path) -> io::Result<()> {
+    image
+        .save_with_format(path, ImageFormat::Png)
+        .map_err(|error| io::Error::new(io::ErrorKind::Other, error))
+}
+
+fn relative_image_path(file_name: &str) -> String {
+    format!("{}/{}", IMAGES_DIRECTORY, file_name)
+}
+
+fn create_html(batch: &BatchResult, report_images: &[ReportImages]) -> String {
+    let mut html: String = String::new();
+    html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str("<title>Image comparison report</title>\n");
+    html.push_str(STYLE);
+    html.push_str("</head>\n<body>\n<h1>Image comparison report</h1>\n");
+    let _ = writeln!(
+        html,
+        "<p>Expected: <code>{}</code><br>Actual: <code>{}</code></p>",
+        escape_markup(&batch.expected_directory.to_string_lossy()),
+        escape_markup(&batch.actual_directory.to_string_lossy())
+    );
+    let _ = writeln!(
+        html,
+        "<p class=\"{}\">{} pairs, {} passed, {} failed</p>",
+        if batch.is_success() { "pass" } else { "fail" },
+        batch.pairs.len(),
+        batch.match_count(),
+        batch.mismatch_count()
+    );
+    write_summary_table(&mut html, batch, report_images);
+    write_unpaired_images(
+        &mut html,
+        "Missing actual images",
+        &batch.missing_actual_images,
+    );
+    write_unpaired_images(
+        &mut html,
+        "Unexpected actual images",
+        &batch.unexpected_actual_images,
+    );
+    for (index, (pair, images)) in batch.pairs.iter().zip(report_images.iter()).enumerate() {
+        write_detail_view(&mut html, index, pair, images);
+    }
+    html.push_str(SCRIPT);
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+fn write_summary_table(html: &mut String, batch: &BatchResult, report_images: &[ReportImages]) {
+    html.push_str("<table>\n<thead><tr><th></th><th>Image</th><th>Result</th>");
+    html.push_str("<th>Difference</th><th>Regions</th></tr></thead>\n<tbody>\n");
+    for (index, (pair, images)) in batch.pairs.iter().zip(report_images.iter()).enumerate() {
+        let thumbnail: String = match &images.thumbnail {
+            Some(thumbnail) => format!(
+                "<a href=\"#pair-{}\"><img class=\"thumbnail\" src=\"{}\" alt=\"\"></a>",
+                index, thumbnail
+            ),
+            None => String::new(),
+        };
+        let _ = writeln!(
+            html,
+            "<tr class=\"{}\"><td>{}</td><td><a href=\"#pair-{}\">{}</a></td><td>{}</td>\
+             <td>{:.2} %</td><td>{}</td></tr>",
+            pass_or_fail(pair),
+            thumbnail,
+            index,
+            escape_markup(&pair.name),
+            result_text(pair),
+            pair.result.difference_percent,
+            pair.result.regions.len()
+        );
+    }
+    html.push_str("</tbody>\n</table>\n");
+}
+
+fn write_unpaired_images(html: &mut String, title: &str, images: &[PathBuf]) {
+    if images.is_empty() {
+        return;
+    }
+    let _ = writeln!(html, "<h2 class=\"fail\">{}</h2>\n<ul>", title);
+    for image in images.iter() {
+        let _ = writeln!(
+            html,
+            "<li><code>{}</code></li>",
+            escape_markup(&image.to_string_lossy())
+        );
+    }
+    html.push_str("</ul>\n");
+}
+
+fn write_detail_view(
+    html: &mut String,
+    index: usize,
+    pair: &ComparisonPair,
+    images: &ReportImages,
+) {
+    let _ = writeln!(
+        html,
+        "<section id=\"pair-{}\" class=\"detail\">\n<h2 class=\"{}\">{}</h2>",
+        index,
+        pass_or_fail(pair),
+        escape_markup(&pair.name)
+    );
+    let _ = writeln!(
+        html,
+        "<p>{}, {:.2} % different pixels, {} regions</p>",
+        result_text(pair),
+        pair.result.difference_percent,
+        pair.result.regions.len()
+    );
+    write_region_table(html, &pair.result.regions);
+    html.push_str("<div class=\"panels\">\n");
+    write_figure(html, "Expected", Some(&images.expected));
+    write_figure(html, "Actual", Some(&images.actual));
+    write_figure(html, "Diff", images.diff.as_deref());
+    html.push_str("</div>\n");
+    let _ = writeln!(
+        html,
+        "<h3>Overlay</h3>\n<div class=\"stack\"><img src=\"{}\" alt=\"expected\">\
+         <img class=\"top overlay\" src=\"{}\" alt=\"actual\"></div>\n\
+         <input type=\"range\" class=\"overlay-slider\" min=\"0\" max=\"100\" value=\"50\">",
+        images.expected, images.actual
+    );
+    let _ = writeln!(
+        html,
+        "<h3>Onion skin</h3>\n<div class=\"stack\"><img src=\"{}\" alt=\"expected\">\
+         <img class=\"top onion\" src=\"{}\" alt=\"actual\"></div>\n\
+         <input type=\"range\" class=\"onion-slider\" min=\"0\" max=\"100\" value=\"50\">",
+        images.expected, images.actual
+    );
+    html.push_str("<p><a href=\"#\">Back to the summary</a></p>\n</section>\n");
+}
+
+fn write_region_table(html: &mut String, regions: &[Region]) {
+    if regions.is_empty() {
+        return;
+    }
+    html.push_str("<table>\n<thead><tr><th>Region</th><th>Pixels</th><th>Severity</th>");
+    html.push_str("<th>Contours</th></tr></thead>\n<tbody>\n");
+    for region in regions.iter() {
+        let _ = writeln!(
+            html,
+            "<tr><td>{}×{} at ({}, {})</td><td>{}</td><td>{:?}</td><td>{}</td></tr>",
+            region.rectangle.get_width(),
+            region.rectangle.get_height(),
+            region.rectangle.min_point.x,
+            region.rectangle.min_point.y,
+            region.pixel_count,
+            region.severity,
+            contour_svg(region)
+        );
+    }
+    html.push_str("</tbody>\n</table>\n");
+}
+
+// the contours in the pixel corner coordinates of the region, empty without trace_contours
+fn contour_svg(region: &Region) -> String {
+    if region.contours.is_empty() {
+        return String::new();
+    }
+    let mut svg: String = format!(
+        "<svg class=\"contours\" viewBox=\"{} {} {} {}\">",
+        region.rectangle.min_point.x,
+        region.rectangle.min_point.y,
+        region.rectangle.get_width(),
+        region.rectangle.get_height()
+    );
+    for contour in region.contours.iter() {
+        let points: Vec<String> = contour
+            .iter()
+            .map(|vertex| format!("{},{}", vertex.x, vertex.y))
+            .collect();
+        let _ = write!(svg, "<polygon points=\"{}\"/>", points.join(" "));
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+fn write_figure(html: &mut String, caption: &str, image: Option<&str>) {
+    let content: String = match image {
+        Some(image) => format!(
+            "<a href=\"{}\"><img src=\"{}\" alt=\"{}\"></a>",
+            image, image, caption
+        ),
+        None => String::from("<p>No differences to show</p>"),
+    };
+    let _ = writeln!(
+        html,
+        "<figure>{}<figcaption>{}</figcaption></figure>",
+        content, caption
+    );
+}
+
+/// Returns a JUnit XML report of the batch with a test case per pair.
+///
+/// The failure message of a mismatching pair contains its state, the percentage of
+/// different pixels and the number of rectangles, and the path of its diff image is
+/// attached as the `diff_image` property. Images without a counterpart are failing test
+/// cases too.
+pub fn junit_report(batch: &BatchResult) -> String {
+    let tests: usize = batch.pairs.len()
+        + batch.missing_actual_images.len()
+        + batch.unexpected_actual_images.len();
+    let failures: usize = tests - batch.match_count();
+    let mut xml: String = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        xml,
+        "<testsuites tests=\"{}\" failures=\"{}\">\n  \
+         <testsuite name=\"image comparison\" tests=\"{}\" failures=\"{}\" errors=\"0\">",
+        tests, failures, tests, failures
+    );
+    for pair in batch.pairs.iter() {
+        let _ = writeln!(
+            xml,
+            "    <testcase classname=\"image_compare\" name=\"{}\">",
+            escape_markup(&pair.name)
+        );
+        if let Some(diff_image) = &pair.diff_image {
+            let _ = writeln!(
+                xml,
+                "      <properties>\n        \
+                 <property name=\"diff_image\" value=\"{}\"/>\n      </properties>",
+                escape_markup(&diff_image.to_string_lossy())
+            );
+        }
+        if !pair.is_match() {
+            let _ = writeln!(
+                xml,
+                "      <failure type=\"{:?}\" message=\"{}\"/>",
+                pair.result.image_comparison_state,
+                escape_markup(&failure_message(pair))
+            );
+        }
+        xml.push_str("    </testcase>\n");
+    }
+    write_unpaired_test_cases(
+        &mut xml,
+        &batch.missing_actual_images,
+        "missing actual image",
+    );
+    write_unpaired_test_cases(
+        &mut xml,
+        &batch.unexpected_actual_images,
+        "no expected image",
+    );
+    xml.push_str("  </testsuite>\n</testsuites>\n");
+    xml
+}
+
+fn write_unpaired_test_cases(xml: &mut String, images: &[PathBuf], message: &str) {
+    for image in images.iter() {
+        let _ = writeln!(
+            xml,
+            "    <testcase classname=\"image_compare\" name=\"{}\">\n      \
+             <failure type=\"Unpaired\" message=\"{}: {}\"/>\n    </testcase>",
+            escape_markup(&file_name(image)),
+            message,
+            escape_markup(&image.to_string_lossy())
+        );
+    }
+}
+
+/// Returns a TAP version 13 report of the batch with a test point per pair.
+///
+/// Failing test points carry the same message as `junit_report` and the diff image path
+/// in a YAML block.
+pub fn tap_report(batch: &BatchResult) -> String {
+    let tests: usize = batch.pairs.len()
+        + batch.missing_actual_images.len()
+        + batch.unexpected_actual_images.len();
+    let mut tap: String = String::from("TAP version 13\n");
+    let _ = writeln!(tap, "1..{}", tests);
+    let mut number: usize = 0;
+    for pair in batch.pairs.iter() {
+        number += 1;
+        if pair.is_match() {
+            let _ = writeln!(tap, "ok {} - {}", number, escape_tap(&pair.name));
+            continue;
+        }
+        let _ = writeln!(tap, "not ok {} - {}", number, escape_tap(&pair.name));
+        tap.push_str("  ---\n");
+        let _ = writeln!(tap, "  message: {}", quote_yaml(&failure_message(pair)));
+        let _ = writeln!(tap, "  state: {:?}", pair.result.image_comparison_state);
+        let _ = writeln!(
+            tap,
+            "  difference_percent: {:.2}",
+            pair.result.difference_percent
+        );
+        let _ = writeln!(tap, "  rectangles: {}", pair.result.rectangles.len());
+        if let Some(diff_image) = &pair.diff_image {
+            let _ = writeln!(
+                tap,
+                "  diff_image: {}",
+                quote_yaml(&diff_image.to_string_lossy())
+            );
+        }
+        tap.push_str("  ...\n");
+    }
+    for (images, message) in [
+        (&batch.missing_actual_images, "missing actual image"),
+        (&batch.unexpected_actual_images, "no expected image"),
+    ] {
+        for image in images.iter() {
+            number += 1;
+            let _ = writeln!(
+                tap,
+                "not ok {} - {}\n  ---\n  message: {}\n  ...",
+                number,
+                escape_tap(&file_name(image)),
+                quote_yaml(&format!("{}: {}", message, image.to_string_lossy()))
+            );
+        }
+    }
+    tap
+}
+
+/// Returns a Markdown summary of the batch for pull-request comments.
+///
+/// The mismatching pairs are listed in a table with their statistics, followed by a
+/// collapsible section per pair with its regions and a link to its diff image. Links are
+/// relative to `base_directory`, the directory of the Markdown file.
+pub fn markdown_report(batch: &BatchResult, base_directory: &Path) -> String {
+    let mut markdown: String = String::from("## Image comparison\n\n");
+    let mismatching_pairs: Vec<&ComparisonPair> =
+        batch.pairs.iter().filter(|pair| !pair.is_match()).collect();
+    let _ = writeln!(
+        markdown,
+        "**{}**: {} pairs, {} passed, {} failed\n",
+        if batch.is_success() {
+            "Passed"
+        } else {
+            "Failed"
+        },
+        batch.pairs.len(),
+        batch.match_count(),
+        batch.mismatch_count()
+    );
+    if !mismatching_pairs.is_empty() {
+        markdown.push_str("| Image | State | Difference | Rectangles |\n");
+        markdown.push_str("| --- | --- | ---: | ---: |\n");
+        for pair in mismatching_pairs.iter() {
+            let _ = writeln!(
+                markdown,
+                "| {} | {:?} | {:.2} % | {} |",
+                escape_markdown_table(&pair.name),
+                pair.result.image_comparison_state,
+                pair.result.difference_percent,
+                pair.result.rectangles.len()
+            );
+        }
+        markdown.push('\n');
+        for pair in mismatching_pairs.iter() {
+            write_markdown_details(&mut markdown, pair, base_directory);
+        }
+    }
+    for (title, images) in [
+        ("Missing actual images", &batch.missing_actual_images),
+        ("Unexpected actual images", &batch.unexpected_actual_images),
+    ] {
+        if images.is_empty() {
+            continue;
+        }
+        let _ = writeln!(markdown, "{}:\n", title);
+        for image in images.iter() {
+            let _ = writeln!(markdown, "- `{}`", image.display());
+        }
+        markdown.push('\n');
+    }
+    markdown
+}
+
+/// Returns a Markdown summary of a single comparison, like `markdown_report`.
+pub fn markdown_pair_report(pair: &ComparisonPair, base_directory: &Path) -> String {
+    let mut markdown: String = String::from("## Image comparison\n\n");
+    if pair.is_match() {
+        let _ = writeln!(markdown, "**Passed**: `{}` matches", pair.name);
+        return markdown;
+    }
+    let _ = writeln!(markdown, "**Failed**: {}\n", failure_message(pair));
+    write_markdown_details(&mut markdown, pair, base_directory);
+    markdown
+}
+
+fn write_markdown_details(markdown: &mut String, pair: &ComparisonPair, base_directory: &Path) {
+    let _ = writeln!(
+        markdown,
+        "<details>\n<summary>{}: {}</summary>\n",
+        escape_markup(&pair.name),
+        escape_markup(&failure_message(pair))
+    );
+    let _ = writeln!(markdown, "- Expected: `{}`", pair.expected_image.display());
+    let _ = writeln!(markdown, "- Actual: `{}`", pair.actual_image.display());
+    if let Some(diff_image) = &pair.diff_image {
+        let _ = writeln!(
+            markdown,
+            "- Diff: [{}]({})",
+            escape_markdown_table(&file_name(diff_image)),
+            markdown_link(&relative_path(diff_image, base_directory))
+        );
+    }
+    let regions: &[Region] = &pair.result.regions;
+    if !regions.is_empty() {
+        // the contours are summarized when they were traced
+        let has_contours: bool = regions.iter().any(|region| !region.contours.is_empty());
+        if has_contours {
+            markdown.push_str("\n| Region | Pixels | Severity | Contours |\n");
+            markdown.push_str("| --- | ---: | --- | --- |\n");
+        } else {
+            markdown.push_str("\n| Region | Pixels | Severity |\n| --- | ---: | --- |\n");
+        }
+        for region in regions.iter().take(MARKDOWN_REGION_LIMIT) {
+            let _ = write!(
+                markdown,
+                "| {}×{} at ({}, {}) | {} | {:?} |",
+                region.rectangle.get_width(),
+                region.rectangle.get_height(),
+                region.rectangle.min_point.x,
+                region.rectangle.min_point.y,
+                region.pixel_count,
+                region.severity
+            );
+            if has_contours {
+                let vertex_count: usize = region.contours.iter().map(Vec::len).sum();
+                let _ = write!(
+                    markdown,
+                    " {} polygons, {} vertices |",
+                    region.contours.len(),
+                    vertex_count
+                );
+            }
+            markdown.push('\n');
+        }
+        if regions.len() > MARKDOWN_REGION_LIMIT {
+            let _ = writeln!(
+                markdown,
+                "\n{} more regions",
+                regions.len() - MARKDOWN_REGION_LIMIT
+            );
+        }
+    }
+    if let Some(diff_image) = &pair.diff_image {
+        let _ = writeln!(
+            markdown,
+            "\n![diff of {}]({})",
+            escape_markdown_table(&pair.name),
+            markdown_link(&relative_path(diff_image, base_directory))
+        );
+    }
+    markdown.push_str("\n</details>\n\n");
+}
+
+// lexical, as the paths may not exist yet; relative paths are resolved against the current
+// directory first
+fn relative_path(path: &Path, base_directory: &Path) -> PathBuf {
+    let path_components: Vec<OsString> = normalized_components(path);
+    let base_components: Vec<OsString> = normalized_components(base_directory);
+    let common: usize = path_components
+        .iter()
+        .zip(base_components.iter())
+        .take_while(|(first, second)| first == second)
+        .count();
+    let mut relative_path: PathBuf = PathBuf::new();
+    for _ in common..base_components.len() {
+        relative_path.push("..");
+    }
+    for component in path_components[common..].iter() {
+        relative_path.push(component);
+    }
+    relative_path
+}
+
+fn normalized_components(path: &Path) -> Vec<OsString> {
+    let absolute_path: PathBuf = match env::current_dir() {
+        Ok(current_directory) if path.is_relative() => current_directory.join(path),
+        _ => path.to_path_buf(),
+    };
+    let mut components: Vec<OsString> = Vec::new();
+    for component in absolute_path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                components.pop();
+            }
+            component => components.push(component.as_os_str().to_os_string()),
+        }
+    }
+    components
+}
+
+// Markdown links end at spaces and unbalanced parentheses, angle brackets and square
+// brackets can start autolinks, HTML and link texts
+fn markdown_link(path: &Path) -> String {
+    let mut link: String = String::new();
+    for (index, component) in path.components().enumerate() {
+        if index > 0 {
+            link.push('/');
+        }
+        for character in component.as_os_str().to_string_lossy().chars() {
+            // escaped one character at a time, so an escaped '%' is not escaped again
+            match character {
+                '%' => link.push_str("%25"),
+                '#' => link.push_str("%23"),
+                '?' => link.push_str("%3F"),
+                ' ' => link.push_str("%20"),
+                '(' => link.push_str("%28"),
+                ')' => link.push_str("%29"),
+                '<' => link.push_str("%3C"),
+                '>' => link.push_str("%3E"),
+                '[' => link.push_str("%5B"),
+                ']' => link.push_str("%5D"),
+                _ => link.push(character),
+            }
+        }
+    }
+    link
+}
+
+fn escape_markdown_table(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+// e.g. "Mismatch: 0.10 % different pixels, 4 rectangles"
+fn failure_message(pair: &ComparisonPair) -> String {
+    format!(
+        "{:?}: {:.2} % different pixels, {} rectangles",
+        pair.result.image_comparison_state,
+        pair.result.difference_percent,
+        pair.result.rectangles.len()
+    )
+}
+
+// '#' starts a directive in a test point description
+fn escape_tap(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('#', "\\#")
+}
+
+fn quote_yaml(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn pass_or_fail(pair: &ComparisonPair) -> &'static str {
+    if pair.is_match() {
+        "pass"
+    } else {
+        "fail"
+    }
+}
+
+fn result_text(pair: &ComparisonPair) -> String {
+    if pair.is_match() {
+        return String::from("pass");
+    }
+    format!("fail ({:?})", pair.result.image_comparison_state)
+}
+
+// for HTML text and attributes, and XML
+pub(crate) fn escape_markup(text: &str) -> String {
+    let mut escaped: String = String::with_capacity(text.len());
+    for character in text.chars() {
+        match character {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(character),
+        }
+    }
+    escaped
+}
+
+const STYLE: &str = "<style>
+body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: left; vertical-align: middle; }
+.pass { color: #1a7f37; }
+.fail { color: #cf222e; }
+img.thumbnail { max-width: 160px; max-height: 160px; }
+.detail { border-top: 2px solid #ccc; margin-top: 2em; }
+.panels { display: flex; flex-wrap: wrap; gap: 1em; }
+figure { margin: 0; }
+figure img { max-width: 400px; border: 1px solid #ccc; }
+.stack { position: relative; display: inline-block; border: 1px solid #ccc; }
+.stack img { display: block; }
+.stack img.top { position: absolute; top: 0; left: 0; }
+img.overlay { clip-path: inset(0 50% 0 0); }
+img.onion { opacity: 0.5; }
+input[type=range] { display: block; width: 400px; }
+svg.contours { width: 80px; height: 80px; }
+svg.contours polygon { fill: rgba(207, 34, 46, 0.2); stroke: #cf222e; vector-effect: non-scaling-stroke; }
+</style>
+";
+
+const SCRIPT: &str = "<script>
+document.querySelectorAll('.overlay-slider').forEach(function (slider) {
+  slider.addEventListener('input', function () {
+    var image = slider.previousElementSibling.querySelector('.top');
+    image.style.clipPath = 'inset(0 ' + (100 - slider.value) + '% 0 0)';
+  });
+});
+document.querySelectorAll('.onion-slider').forEach(function (slider) {
+  slider.addEventListener('input', function () {
+    var image = slider.previousElementSibling.querySelector('.top');
+    image.style.opacity = slider.value / 100;
+  });
+});
+</script>
+";
+
+#[cfg(test)]
+mod tests {
+    use super::{contour_svg, escape_markup, escape_tap, markdown_link, quote_yaml, relative_path};
+    use crate::model::{Point, Rectangle, Region, Severity};
+    use std::path::{Path, PathBuf};
+    #[test]
+    fn escape_markup_special_characters() {
+        assert_eq!(
+            "a &lt;b&gt; &amp; &quot;c&quot; &#39;d&#39;",
+            escape_markup("a <b> & \"c\" 'd'")
+        );
+    }
+    #[test]
+    fn escape_tap_directives() {
+        assert_eq!("a \\# TODO \\\\", escape_tap("a # TODO \\"));
+        assert_eq!("\"a \\\"b\\\" \\\\\"", quote_yaml("a \"b\" \\"));
+    }
+    #[test]
+    fn relative_markdown_links() {
+        assert_eq!(
+            PathBuf::from("diff/a b.png"),
+            relative_path(Path::new("/ci/out/diff/a b.png"), Path::new("/ci/out"))
+        );
+        assert_eq!(
+            PathBuf::from("../diff/a.png"),
+            relative_path(Path::new("/ci/diff/a.png"), Path::new("/ci/./out"))
+        );
+        assert_eq!(
+            PathBuf::from("a.png"),
+            relative_path(Path::new("/ci/diff/a.png"), Path::new("/ci/out/../diff"))
+        );
+        assert_eq!(
+            "../diff/a%20%281%29.png",
+            markdown_link(Path::new("../diff/a (1).png"))
+        );
+        assert_eq!(
+            "diff/%3Ca%3E%5B1%5D.png",
+            markdown_link(Path::new("diff/<a>[1].png"))
+        );
+    }
+    #[test]
+    fn markdown_links_escape_percent_hash_and_question_mark() {
+        // the name would otherwise be read as an escaped space, a fragment and a query
+        assert_eq!(
+            "diff/100%2520%25%23a%3F.png",
+            markdown_link(Path::new("diff/100%20%#a?.png"))
+        );
+    }
+    #[test]
+    fn contour_svg_polygons() {
+        let mut region: Region = Region {
+            rectangle: Rectangle::create_with_coordinates(1, 1, 2, 2),
+            contours: Vec::new(),
+            pixel_count: 4,
+            centroid: (1.5, 1.5),
+            mean_color_delta: 255f64,
+            max_color_delta: 255f64,
+            severity_score: 4f64,
+            severity: Severity::Low,
+        };
+        assert_eq!("", contour_svg(&region));
+        region.contours = vec![vec![
+            Point::new(1, 1),
+            Point::new(3, 1),
+            Point::new(3, 3),
+            Point::new(1, 3),
+        ]];
+        assert_eq!(
+            "<svg class=\"contours\" viewBox=\"1 1 2 2\">\
+             <polygon points=\"1,1 3,1 3,3 1,3\"/></svg>",
+            contour_svg(&region)
+        );
+    }
+}
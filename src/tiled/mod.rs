@@ -0,0 +1,305 @@
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+
+use crate::core::merge_rectangles;
+use crate::grouping::ThresholdLabeling;
+use crate::model::{
+    ImageComparisonOptions, ImageComparisonResult, ImageComparisonState, Rectangle,
+};
+use crate::pixel::PixelComparison;
+use crate::render::outline_rectangles;
+use png::{BitDepth, ColorType, Decoder, Encoder, Reader};
+
+/// Compares two PNG images band by band without decoding them completely, for images that
+/// do not fit in memory.
+///
+/// Only `band_height` rows of each image are held at a time. Different pixels are joined
+/// into regions like with `compare_images`, up to `threshold` pixels apart along rows,
+/// columns and diagonals, also across band borders, so the rectangles are the same. When
+/// `result_image` is set and the images differ, the actual image is written there band by
+/// band with the rectangles outlined like `RenderStyle::Outline`.
+///
+/// The threshold, tolerance, color difference, minimal rectangle size, allowed percentage
+/// and merge distance of `options` are used, the other options are not supported.
+/// The result contains no regions and can not be rendered.
+pub fn compare_images_tiled(
+    expected_image: &OsStr,
+    actual_image: &OsStr,
+    result_image: Option<&OsStr>,
+    band_height: u32,
+    options: &ImageComparisonOptions,
+) -> ImageComparisonResult {
+    let mut expected_reader: Reader<BufReader<File>> = match open_reader(expected_image) {
+        Ok(reader) => reader,
+        Err(image_comparison_state) => {
+            return ImageComparisonResult::create_with_state(image_comparison_state)
+        }
+    };
+    let mut actual_reader: Reader<BufReader<File>> = match open_reader(actual_image) {
+        Ok(reader) => reader,
+        Err(image_comparison_state) => {
+            return ImageComparisonResult::create_with_state(image_comparison_state)
+        }
+    };
+    let expected_dimensions: (u32, u32) = expected_reader.info().size();
+    let actual_dimensions: (u32, u32) = actual_reader.info().size();
+    let mut image_comparison_result: ImageComparisonResult =
+        ImageComparisonResult::create_with_state(ImageComparisonState::Match);
+    image_comparison_result.expected_dimensions = expected_dimensions;
+    image_comparison_result.actual_dimensions = actual_dimensions;
+    if expected_dimensions != actual_dimensions {
+        eprintln!(
+            "expected image dimensions: {:?} and actual image dimensions: {:?} are not equal",
+            expected_dimensions, actual_dimensions
+        );
+        image_comparison_result.image_comparison_state = ImageComparisonState::SizeMismatch;
+        image_comparison_result.is_size_mismatch = true;
+        return image_comparison_result;
+    }
+    let (width, height) = expected_dimensions;
+    let band_height: usize = band_height.max(1) as usize;
+    let mut threshold_labeling: ThresholdLabeling =
+        ThresholdLabeling::new(width as usize, options.threshold as usize, false);
+    let pixel_comparison: PixelComparison =
+        PixelComparison::create_with_options(options, options.color_tolerance);
+    let mut count_of_different_pixels: usize = 0;
+    let mut y: usize = 0;
+    while y < height as usize {
+        let expected_band: Vec<Vec<u8>> = match read_band(&mut expected_reader, band_height) {
+            Ok(band) => band,
+            Err(image_comparison_state) => {
+                image_comparison_result.image_comparison_state = image_comparison_state;
+                return image_comparison_result;
+            }
+        };
+        let actual_band: Vec<Vec<u8>> = match read_band(&mut actual_reader, band_height) {
+            Ok(band) => band,
+            Err(image_comparison_state) => {
+                image_comparison_result.image_comparison_state = image_comparison_state;
+                return image_comparison_result;
+            }
+        };
+        if expected_band.is_empty() || expected_band.len() != actual_band.len() {
+            eprintln!("image data ends before row {}", y);
+            image_comparison_result.image_comparison_state =
+                ImageComparisonState::FormatNotSupported;
+            return image_comparison_result;
+        }
+        for (expected_row, actual_row) in expected_band.iter().zip(actual_band.iter()) {
+            let different_pixels: Vec<bool> = expected_row
+                .chunks_exact(4)
+                .zip(actual_row.chunks_exact(4))
+                .map(|(e, a)| {
+                    pixel_comparison.differs(&[e[0], e[1], e[2], e[3]], &[a[0], a[1], a[2], a[3]])
+                })
+                .collect();
+            count_of_different_pixels += threshold_labeling.push_row(&different_pixels);
+            y += 1;
+        }
+    }
+    let total_pixel_count: usize = width as usize * height as usize;
+    if total_pixel_count > 0 {
+        image_comparison_result.difference_percent =
+            count_of_different_pixels as f64 / total_pixel_count as f64 * 100f64;
+    }
+    if count_of_different_pixels == 0
+        || image_comparison_result.difference_percent
+            <= options.allowing_percent_of_different_pixels
+    {
+        return image_comparison_result;
+    }
+    let rectangles: Vec<Rectangle> = threshold_labeling
+        .finish()
+        .into_iter()
+        .map(|pixel_region| pixel_region.rectangle)
+        .filter(|rectangle| rectangle.size() >= options.minimal_rectangle_size as usize)
+        .collect();
+    let rectangles: Vec<Rectangle> = if options.merge_rectangles {
+        merge_rectangles(rectangles, options.merge_distance as usize)
+    } else {
+        rectangles
+    };
+    if rectangles.is_empty() {
+        return image_comparison_result;
+    }
+    image_comparison_result.image_comparison_state = ImageComparisonState::Mismatch;
+    if let Some(result_image) = result_image {
+        if let Err(image_comparison_state) = write_result_image(
+            actual_image,
+            result_image,
+            &rectangles,
+            band_height,
+            [255, 0, 0, 255],
+        ) {
+            image_comparison_result.image_comparison_state = image_comparison_state;
+        }
+    }
+    image_comparison_result.rectangles = rectangles;
+    image_comparison_result
+}
+
+// only non-interlaced 8-bit RGBA images can be read row by row in image order
+pub(crate) fn open_reader(image: &OsStr) -> Result<Reader<BufReader<File>>, ImageComparisonState> {
+    let file: File = File::open(image).map_err(|error| {
+        eprintln!("image: {:?} can not be opened: {}", image, error);
+        ImageComparisonState::FormatNotSupported
+    })?;
+    let reader: Reader<BufReader<File>> =
+        Decoder::new(BufReader::new(file))
+            .read_info()
+            .map_err(|error| {
+                eprintln!("image: {:?} is not a png image: {}", image, error);
+                ImageComparisonState::FormatNotSupported
+            })?;
+    if reader.info().interlaced {
+        eprintln!("interlaced image: {:?} is not supported", image);
+        return Err(ImageComparisonState::FormatNotSupported);
+    }
+    if reader.output_color_type() != (ColorType::Rgba, BitDepth::Eight) {
+        eprintln!(
+            "image colortype: {:?} is not supported",
+            reader.output_color_type()
+        );
+        return Err(ImageComparisonState::ColorTypeNotSupported);
+    }
+    Ok(reader)
+}
+
+fn read_band(
+    reader: &mut Reader<BufReader<File>>,
+    band_height: usize,
+) -> Result<Vec<Vec<u8>>, ImageComparisonState> {
+    let mut band: Vec<Vec<u8>> = Vec::new();
+    while band.len() < band_height {
+        match reader.next_row() {
+            Ok(Some(row)) => band.push(row.data().to_vec()),
+            Ok(None) => break,
+            Err(error) => {
+                eprintln!("image data can not be decoded: {}", error);
+                return Err(ImageComparisonState::FormatNotSupported);
+            }
+        }
+    }
+    Ok(band)
+}
+
+fn write_result_image(
+    actual_image: &OsStr,
+    result_image: &OsStr,
+    rectangles: &[Rectangle],
+    band_height: usize,
+    color: [u8; 4],
+) -> Result<(), ImageComparisonState> {
+    let mut reader: Reader<BufReader<File>> = open_reader(actual_image)?;
+    let (width, height) = reader.info().size();
+    let file: File = File::create(result_image).map_err(|error| {
+        eprintln!(
+            "result image: {:?} can not be created: {}",
+            result_image, error
+        );
+        ImageComparisonState::FormatNotSupported
+    })?;
+    let mut encoder: Encoder<BufWriter<File>> = Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(ColorType::Rgba);
+    encoder.set_depth(BitDepth::Eight);
+    let write_error = |error: png::EncodingError| {
+        eprintln!(
+            "result image: {:?} can not be written: {}",
+            result_image, error
+        );
+        ImageComparisonState::FormatNotSupported
+    };
+    let mut stream_writer = encoder
+        .write_header()
+        .and_then(|writer| writer.into_stream_writer())
+        .map_err(write_error)?;
+    let outline_rectangles: Vec<Rectangle> = rectangles
+        .iter()
+        .flat_map(|rectangle| outline_rectangles(rectangle, width, height))
+        .collect();
+    let mut y: usize = 0;
+    loop {
+        let mut band: Vec<Vec<u8>> = read_band(&mut reader, band_height)?;
+        if band.is_empty() {
+            break;
+        }
+        for row in band.iter_mut() {
+            draw_rectangles_on_row(row, y, &outline_rectangles, color);
+            stream_writer
+                .write_all(row)
+                .map_err(|error| write_error(error.into()))?;
+            y += 1;
+        }
+    }
+    stream_writer.finish().map_err(write_error)
+}
+
+fn draw_rectangles_on_row(row: &mut [u8], y: usize, rectangles: &[Rectangle], color: [u8; 4]) {
+    for rectangle in rectangles.iter() {
+        if y < rectangle.min_point.y || y > rectangle.max_point.y {
+            continue;
+        }
+        if y == rectangle.min_point.y || y == rectangle.max_point.y {
+            for x in rectangle.min_point.x..=rectangle.max_point.x {
+                row[x * 4..x * 4 + 4].copy_from_slice(&color);
+            }
+        } else {
+            for x in [rectangle.min_point.x, rectangle.max_point.x] {
+                row[x * 4..x * 4 + 4].copy_from_slice(&color);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::grouping::{PixelRegion, ThresholdLabeling};
+    use crate::model::Rectangle;
+    // labels without collecting runs, like compare_images_tiled
+    fn label_rows(rows: &[&[u8]], threshold: usize) -> Vec<(usize, usize, usize, usize)> {
+        let mut threshold_labeling: ThresholdLabeling =
+            ThresholdLabeling::new(rows[0].len(), threshold, false);
+        for row in rows.iter() {
+            let different_pixels: Vec<bool> = row.iter().map(|value| *value == 1).collect();
+            threshold_labeling.push_row(&different_pixels);
+        }
+        threshold_labeling
+            .finish()
+            .iter()
+            .map(|pixel_region: &PixelRegion| {
+                assert!(pixel_region.runs.is_empty());
+                let rectangle: &Rectangle = &pixel_region.rectangle;
+                (
+                    rectangle.min_point.x,
+                    rectangle.min_point.y,
+                    rectangle.max_point.x,
+                    rectangle.max_point.y,
+                )
+            })
+            .collect()
+    }
+    #[test]
+    fn threshold_labeling_joins_u_shape() {
+        // the arms get different labels first and are joined by the last row
+        let rows: [&[u8]; 3] = [&[1, 0, 0, 1], &[1, 0, 0, 1], &[1, 1, 1, 1]];
+        assert_eq!(vec![(0, 0, 3, 2)], label_rows(&rows, 1));
+    }
+    #[test]
+    fn threshold_labeling_threshold() {
+        let rows: [&[u8]; 4] = [&[1, 0, 0, 0], &[0, 0, 0, 0], &[0, 0, 0, 0], &[0, 0, 0, 1]];
+        assert_eq!(vec![(0, 0, 0, 0), (3, 3, 3, 3)], label_rows(&rows, 2));
+        assert_eq!(vec![(0, 0, 3, 3)], label_rows(&rows, 3));
+    }
+    #[test]
+    fn threshold_labeling_joins_along_rays() {
+        // (1, 2) is within the threshold of (0, 0) but not along a row, column or diagonal
+        let rows: [&[u8]; 3] = [&[1, 0], &[0, 0], &[0, 1]];
+        assert_eq!(vec![(0, 0, 0, 0), (1, 2, 1, 2)], label_rows(&rows, 2));
+    }
+    #[test]
+    fn threshold_labeling_threshold_zero() {
+        let rows: [&[u8]; 2] = [&[1, 1], &[1, 0]];
+        assert_eq!(3, label_rows(&rows, 0).len());
+    }
+}
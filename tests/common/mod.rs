@@ -1,5 +1,5 @@
 use std::{
-    env,
+    env, fs,
     io::{Error, ErrorKind, Result},
     path::{Path, PathBuf},
 };
@@ -18,3 +18,32 @@ pub(crate) fn get_tests_resources_directory() -> Result<PathBuf> {
         Err(Error::new(ErrorKind::NotFound, message.as_str()))
     }
 }
+
+// expected and actual directories in the temporary directory, with a matching pair, a
+// mismatching pair and one image without a counterpart on each side
+pub(crate) fn create_batch_directories(name: &str) -> Result<(PathBuf, PathBuf)> {
+    let tests_resources_directory: PathBuf = get_tests_resources_directory()?;
+    let batch_directory: PathBuf = env::temp_dir().join(format!("image_compare_{}", name));
+    if batch_directory.try_exists()? {
+        fs::remove_dir_all(&batch_directory)?;
+    }
+    let expected_directory: PathBuf = batch_directory.join("expected");
+    let actual_directory: PathBuf = batch_directory.join("actual");
+    fs::create_dir_all(&expected_directory)?;
+    fs::create_dir_all(&actual_directory)?;
+    let files: [(&str, &PathBuf, &str); 6] = [
+        ("expected_same.png", &expected_directory, "same.png"),
+        ("actual_same.png", &actual_directory, "same.png"),
+        ("expected-4.png", &expected_directory, "changed.png"),
+        ("actual-4.png", &actual_directory, "changed.png"),
+        ("expected-1.png", &expected_directory, "only-expected.png"),
+        ("actual-1.png", &actual_directory, "only-actual.png"),
+    ];
+    for (source, directory, target) in files.iter() {
+        fs::copy(
+            tests_resources_directory.join(source),
+            directory.join(target),
+        )?;
+    }
+    Ok((expected_directory, actual_directory))
+}
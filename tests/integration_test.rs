@@ -1,7 +1,19 @@
-use crate::common::get_tests_resources_directory;
-use image::{open, RgbaImage};
-use image_compare::{compare_images, ImageComparisonResult, ImageComparisonState};
+use crate::common::{create_batch_directories, get_tests_resources_directory};
+use image::imageops::FilterType;
+use image::{open, Rgba, RgbaImage};
+use image_compare::{
+    compare_directories, compare_images, compare_images_tiled, compare_images_with_options,
+    images_are_identical, junit_report, markdown_pair_report, markdown_report, tap_report,
+    write_html_report, BatchResult, BoundingBoxGrouping, ColorDifference, Comparator,
+    ComparisonPair, ConnectedComponentGrouping, DeltaEStatistics, DifferenceGrid,
+    ExactPixelComparator, GridCellGrouping, ImageComparisonOptions, ImageComparisonResult,
+    ImageComparisonState, MorphologicalOperation, PixelComparator, Point, Rectangle, Region,
+    RegionGrouping, RenderStyle, Severity, SizeMismatchPolicy, ThresholdGrouping,
+};
+use std::fs;
 use std::path::PathBuf;
+use std::process::{Command, Output};
+use std::sync::Arc;
 
 mod common;
 
@@ -57,6 +69,9 @@ fn validate_dimensions_fail() {
                 result.image_comparison_state,
                 ImageComparisonState::SizeMismatch
             );
+            assert_eq!((972, 1967), result.expected_dimensions);
+            assert_eq!((740, 529), result.actual_dimensions);
+            assert!(result.is_size_mismatch);
         }
         Err(error) => eprintln!("{}", error),
     }
@@ -97,6 +112,1599 @@ fn compare_images_match() {
     test_compare_images_match("expected_same.png", "actual_same.png");
 }
 
+#[test]
+fn compare_images_mask_covers_differences() {
+    match get_tests_resources_directory() {
+        Ok(tests_resources_directory) => {
+            let mut expected: PathBuf = tests_resources_directory.clone();
+            expected.push("expected-2.png");
+            let mut actual: PathBuf = tests_resources_directory.clone();
+            actual.push("actual-2.png");
+            let mut mask: PathBuf = tests_resources_directory.clone();
+            mask.push("mask-2.png");
+            let options: ImageComparisonOptions = ImageComparisonOptions {
+                mask_image: Some(mask),
+                ..Default::default()
+            };
+            let image_comparison_result: ImageComparisonResult =
+                compare_images_with_options(expected.as_os_str(), actual.as_os_str(), &options);
+            assert_eq!(
+                image_comparison_result.image_comparison_state,
+                ImageComparisonState::Match
+            );
+            assert_eq!(0f64, image_comparison_result.difference_percent);
+            assert!(image_comparison_result
+                .render(RenderStyle::Outline)
+                .is_none());
+        }
+        Err(error) => eprintln!("{}", error),
+    }
+}
+
+#[test]
+fn compare_images_opaque_rgba_mask() {
+    match get_tests_resources_directory() {
+        Ok(tests_resources_directory) => {
+            let mut expected: PathBuf = tests_resources_directory.clone();
+            expected.push("expected-2.png");
+            let mut actual: PathBuf = tests_resources_directory.clone();
+            actual.push("actual-2.png");
+            let mut mask: PathBuf = tests_resources_directory.clone();
+            mask.push("mask-2-partial.png");
+            // the same black and white mask with an opaque alpha channel
+            let rgba_mask: PathBuf =
+                std::env::temp_dir().join("image_compare_opaque_rgba_mask.png");
+            open(&mask).unwrap().into_rgba8().save(&rgba_mask).unwrap();
+            let options: ImageComparisonOptions = ImageComparisonOptions {
+                mask_image: Some(mask),
+                ..Default::default()
+            };
+            let image_comparison_result: ImageComparisonResult =
+                compare_images_with_options(expected.as_os_str(), actual.as_os_str(), &options);
+            let rgba_options: ImageComparisonOptions = ImageComparisonOptions {
+                mask_image: Some(rgba_mask),
+                ..Default::default()
+            };
+            let rgba_image_comparison_result: ImageComparisonResult = compare_images_with_options(
+                expected.as_os_str(),
+                actual.as_os_str(),
+                &rgba_options,
+            );
+            assert_eq!(
+                rgba_image_comparison_result.image_comparison_state,
+                ImageComparisonState::Mismatch
+            );
+            assert_eq!(
+                image_comparison_result.difference_percent,
+                rgba_image_comparison_result.difference_percent
+            );
+            assert_eq!(
+                image_comparison_result.rectangles.len(),
+                rgba_image_comparison_result.rectangles.len()
+            );
+        }
+        Err(error) => eprintln!("{}", error),
+    }
+}
+
+#[test]
+fn compare_images_mask_partial_shaded() {
+    match get_tests_resources_directory() {
+        Ok(tests_resources_directory) => {
+            let mut expected: PathBuf = tests_resources_directory.clone();
+            expected.push("expected-2.png");
+            let mut actual: PathBuf = tests_resources_directory.clone();
+            actual.push("actual-2.png");
+            let mut mask: PathBuf = tests_resources_directory.clone();
+            mask.push("mask-2-partial.png");
+            let unmasked_result: ImageComparisonResult =
+                compare_images(expected.as_os_str(), actual.as_os_str());
+            let options: ImageComparisonOptions = ImageComparisonOptions {
+                mask_image: Some(mask),
+                shade_masked_area: true,
+                ..Default::default()
+            };
+            let image_comparison_result: ImageComparisonResult =
+                compare_images_with_options(expected.as_os_str(), actual.as_os_str(), &options);
+            assert_eq!(
+                image_comparison_result.image_comparison_state,
+                ImageComparisonState::Mismatch
+            );
+            assert!(image_comparison_result.difference_percent > 0f64);
+            assert!(
+                image_comparison_result.difference_percent < unmasked_result.difference_percent
+            );
+            let actual_image: RgbaImage = open(actual).unwrap().into_rgba8();
+            let result_image: RgbaImage = image_comparison_result
+                .render(RenderStyle::Outline)
+                .expect("result_image is missing from image_comparison_result");
+            assert_ne!(
+                actual_image.get_pixel(70, 100),
+                result_image.get_pixel(70, 100)
+            );
+            assert_eq!(actual_image.get_pixel(0, 0), result_image.get_pixel(0, 0));
+        }
+        Err(error) => eprintln!("{}", error),
+    }
+}
+
+#[test]
+fn compare_images_ignore_color_hatched() {
+    match get_tests_resources_directory() {
+        Ok(tests_resources_directory) => {
+            let mut expected: PathBuf = tests_resources_directory.clone();
+            expected.push("expected-2-color-key.png");
+            let mut actual: PathBuf = tests_resources_directory.clone();
+            actual.push("actual-2.png");
+            let options: ImageComparisonOptions = ImageComparisonOptions {
+                ignore_color: Some(Rgba::<u8>([255, 0, 255, 255])),
+                ignore_color_tolerance: 5,
+                ..Default::default()
+            };
+            let image_comparison_result: ImageComparisonResult =
+                compare_images_with_options(expected.as_os_str(), actual.as_os_str(), &options);
+            assert_eq!(
+                image_comparison_result.image_comparison_state,
+                ImageComparisonState::Mismatch
+            );
+            let actual_image: RgbaImage = open(actual).unwrap().into_rgba8();
+            let result_image: RgbaImage = image_comparison_result
+                .render(RenderStyle::Outline)
+                .expect("result_image is missing from image_comparison_result");
+            assert_eq!(
+                &Rgba::<u8>([64, 64, 64, 255]),
+                result_image.get_pixel(66, 90)
+            );
+            assert_eq!(actual_image.get_pixel(0, 0), result_image.get_pixel(0, 0));
+        }
+        Err(error) => eprintln!("{}", error),
+    }
+}
+
+#[test]
+fn compare_images_ignore_color_not_matching_tolerance() {
+    match get_tests_resources_directory() {
+        Ok(tests_resources_directory) => {
+            let mut expected: PathBuf = tests_resources_directory.clone();
+            expected.push("expected-2-color-key.png");
+            let mut actual: PathBuf = tests_resources_directory.clone();
+            actual.push("actual-2.png");
+            let options: ImageComparisonOptions = ImageComparisonOptions {
+                ignore_color: Some(Rgba::<u8>([255, 0, 255, 255])),
+                ..Default::default()
+            };
+            let image_comparison_result: ImageComparisonResult =
+                compare_images_with_options(expected.as_os_str(), actual.as_os_str(), &options);
+            let result_image: RgbaImage = image_comparison_result
+                .render(RenderStyle::Outline)
+                .expect("result_image is missing from image_comparison_result");
+            assert_ne!(
+                &Rgba::<u8>([64, 64, 64, 255]),
+                result_image.get_pixel(66, 90)
+            );
+        }
+        Err(error) => eprintln!("{}", error),
+    }
+}
+
+#[test]
+fn compare_images_included_areas_without_differences() {
+    match get_tests_resources_directory() {
+        Ok(tests_resources_directory) => {
+            let mut expected: PathBuf = tests_resources_directory.clone();
+            expected.push("expected-2.png");
+            let mut actual: PathBuf = tests_resources_directory.clone();
+            actual.push("actual-2.png");
+            let options: ImageComparisonOptions = ImageComparisonOptions {
+                included_areas: vec![Rectangle::create_with_coordinates(0, 0, 50, 50)],
+                ..Default::default()
+            };
+            let image_comparison_result: ImageComparisonResult =
+                compare_images_with_options(expected.as_os_str(), actual.as_os_str(), &options);
+            assert_eq!(
+                image_comparison_result.image_comparison_state,
+                ImageComparisonState::Match
+            );
+            assert!(image_comparison_result
+                .render(RenderStyle::Outline)
+                .is_none());
+        }
+        Err(error) => eprintln!("{}", error),
+    }
+}
+
+#[test]
+fn compare_images_included_areas_cropped() {
+    match get_tests_resources_directory() {
+        Ok(tests_resources_directory) => {
+            let mut expected: PathBuf = tests_resources_directory.clone();
+            expected.push("expected-2.png");
+            let mut actual: PathBuf = tests_resources_directory.clone();
+            actual.push("actual-2.png");
+            let options: ImageComparisonOptions = ImageComparisonOptions {
+                included_areas: vec![Rectangle::create_with_coordinates(60, 60, 109, 109)],
+                crop_to_included_areas: true,
+                crop_margin: 10,
+                ..Default::default()
+            };
+            let image_comparison_result: ImageComparisonResult =
+                compare_images_with_options(expected.as_os_str(), actual.as_os_str(), &options);
+            assert_eq!(
+                image_comparison_result.image_comparison_state,
+                ImageComparisonState::Mismatch
+            );
+            assert_eq!(
+                403f64 / 2500f64 * 100f64,
+                image_comparison_result.difference_percent
+            );
+            let result_image: RgbaImage = image_comparison_result
+                .render(RenderStyle::Outline)
+                .expect("result_image is missing from image_comparison_result");
+            assert_eq!((70, 70), result_image.dimensions());
+        }
+        Err(error) => eprintln!("{}", error),
+    }
+}
+
+#[test]
+fn compare_images_size_mismatch_compare_overlap() {
+    let options: ImageComparisonOptions = ImageComparisonOptions {
+        size_mismatch_policy: SizeMismatchPolicy::CompareOverlap,
+        ..Default::default()
+    };
+    if let Some(image_comparison_result) =
+        test_compare_images_with_options("expected-7.png", "actual-7.png", &options)
+    {
+        assert_eq!(
+            image_comparison_result.image_comparison_state,
+            ImageComparisonState::Mismatch
+        );
+        assert_eq!((100, 80), image_comparison_result.expected_dimensions);
+        assert_eq!((110, 80), image_comparison_result.actual_dimensions);
+        assert_eq!(0f64, image_comparison_result.difference_percent);
+        let result_image: RgbaImage = image_comparison_result
+            .render(RenderStyle::Outline)
+            .expect("result_image is missing from image_comparison_result");
+        assert_eq!((110, 80), result_image.dimensions());
+        assert_eq!(
+            &Rgba::<u8>([255, 0, 0, 255]),
+            result_image.get_pixel(100, 40)
+        );
+    }
+}
+
+#[test]
+fn compare_images_size_mismatch_pad() {
+    let options: ImageComparisonOptions = ImageComparisonOptions {
+        size_mismatch_policy: SizeMismatchPolicy::Pad(Rgba::<u8>([0, 0, 0, 255])),
+        ..Default::default()
+    };
+    if let Some(image_comparison_result) =
+        test_compare_images_with_options("expected-7.png", "actual-7.png", &options)
+    {
+        assert_eq!(
+            image_comparison_result.image_comparison_state,
+            ImageComparisonState::Mismatch
+        );
+        assert_eq!(
+            10f64 * 80f64 / (110f64 * 80f64) * 100f64,
+            image_comparison_result.difference_percent
+        );
+    }
+}
+
+#[test]
+fn compare_images_size_mismatch_merge_rectangles() {
+    let rectangle_count = |merge_rectangles: bool| -> Option<usize> {
+        let options: ImageComparisonOptions = ImageComparisonOptions {
+            size_mismatch_policy: SizeMismatchPolicy::Pad(Rgba::<u8>([0, 0, 0, 255])),
+            merge_rectangles,
+            ..Default::default()
+        };
+        test_compare_images_with_options("expected-7.png", "actual-7.png", &options)
+            .map(|image_comparison_result| image_comparison_result.rectangles.len())
+    };
+    if let (Some(merged), Some(not_merged)) = (rectangle_count(true), rectangle_count(false)) {
+        // the padded pixels and the size mismatch area are reported apart without merging
+        assert_eq!(1, merged);
+        assert_eq!(2, not_merged);
+    }
+}
+
+#[test]
+fn compare_images_size_mismatch_scale() {
+    let options: ImageComparisonOptions = ImageComparisonOptions {
+        size_mismatch_policy: SizeMismatchPolicy::Scale,
+        ..Default::default()
+    };
+    if let Some(image_comparison_result) =
+        test_compare_images_with_options("expected-7.png", "actual-7.png", &options)
+    {
+        assert_ne!(
+            image_comparison_result.image_comparison_state,
+            ImageComparisonState::SizeMismatch
+        );
+        assert!(image_comparison_result.is_size_mismatch);
+        if let Some(result_image) = image_comparison_result.render(RenderStyle::Outline) {
+            assert_eq!((100, 80), result_image.dimensions());
+        }
+    }
+}
+
+#[test]
+fn compare_images_resample_match() {
+    let options: ImageComparisonOptions = ImageComparisonOptions {
+        size_mismatch_policy: SizeMismatchPolicy::Resample(FilterType::Nearest),
+        ..Default::default()
+    };
+    if let Some(image_comparison_result) =
+        test_compare_images_with_options("expected-7.png", "actual-8-2x.png", &options)
+    {
+        assert_eq!(
+            image_comparison_result.image_comparison_state,
+            ImageComparisonState::Match
+        );
+    }
+}
+
+#[test]
+fn compare_images_resample_mismatch() {
+    let options: ImageComparisonOptions = ImageComparisonOptions {
+        size_mismatch_policy: SizeMismatchPolicy::Resample(FilterType::Nearest),
+        ..Default::default()
+    };
+    if let Some(image_comparison_result) =
+        test_compare_images_with_options("expected-7.png", "actual-9-2x.png", &options)
+    {
+        assert_eq!(
+            image_comparison_result.image_comparison_state,
+            ImageComparisonState::Mismatch
+        );
+        assert_eq!(1, image_comparison_result.rectangles.len());
+        assert!(Rectangle::create_with_coordinates(50, 30, 59, 39)
+            .equals(&image_comparison_result.rectangles[0]));
+        assert_eq!(1, image_comparison_result.high_resolution_rectangles.len());
+        assert!(Rectangle::create_with_coordinates(100, 60, 119, 79)
+            .equals(&image_comparison_result.high_resolution_rectangles[0]));
+    }
+}
+
+#[test]
+fn compare_images_shift_detected() {
+    let options: ImageComparisonOptions = ImageComparisonOptions {
+        shift_search_radius: 3,
+        ..Default::default()
+    };
+    if let Some(image_comparison_result) =
+        test_compare_images_with_options("expected-7.png", "actual-10.png", &options)
+    {
+        assert_eq!(
+            image_comparison_result.image_comparison_state,
+            ImageComparisonState::Match
+        );
+        assert_eq!((2, 1), image_comparison_result.detected_offset);
+        assert_eq!(2, image_comparison_result.exposed_rectangles.len());
+        assert!(Rectangle::create_with_coordinates(0, 0, 1, 79)
+            .equals(&image_comparison_result.exposed_rectangles[0]));
+        assert!(Rectangle::create_with_coordinates(2, 0, 99, 0)
+            .equals(&image_comparison_result.exposed_rectangles[1]));
+    }
+}
+
+#[test]
+fn compare_images_shift_detected_mismatch() {
+    let options: ImageComparisonOptions = ImageComparisonOptions {
+        shift_search_radius: 3,
+        ..Default::default()
+    };
+    if let Some(image_comparison_result) =
+        test_compare_images_with_options("expected-7.png", "actual-11.png", &options)
+    {
+        assert_eq!(
+            image_comparison_result.image_comparison_state,
+            ImageComparisonState::Mismatch
+        );
+        assert_eq!((2, 1), image_comparison_result.detected_offset);
+        assert_eq!(1, image_comparison_result.rectangles.len());
+        let result_image: RgbaImage = image_comparison_result
+            .render(RenderStyle::Outline)
+            .expect("result_image is missing from image_comparison_result");
+        assert_eq!(&Rgba::<u8>([0, 0, 255, 255]), result_image.get_pixel(1, 40));
+    }
+}
+
+#[test]
+fn compare_images_neighborhood_radius_match() {
+    let options: ImageComparisonOptions = ImageComparisonOptions {
+        neighborhood_radius: 1,
+        ..Default::default()
+    };
+    if let Some(image_comparison_result) =
+        test_compare_images_with_options("expected-7.png", "actual-12.png", &options)
+    {
+        assert_eq!(
+            image_comparison_result.image_comparison_state,
+            ImageComparisonState::Match
+        );
+    }
+}
+
+#[test]
+fn compare_images_neighborhood_radius_mismatch() {
+    let options: ImageComparisonOptions = ImageComparisonOptions {
+        neighborhood_radius: 0,
+        ..Default::default()
+    };
+    if let Some(image_comparison_result) =
+        test_compare_images_with_options("expected-7.png", "actual-12.png", &options)
+    {
+        assert_eq!(
+            image_comparison_result.image_comparison_state,
+            ImageComparisonState::Mismatch
+        );
+    }
+}
+
+#[test]
+fn compare_images_delta_e_mismatch() {
+    let options: ImageComparisonOptions = ImageComparisonOptions {
+        color_difference: ColorDifference::Ciede2000,
+        delta_e_threshold: 2.3,
+        ..Default::default()
+    };
+    if let Some(image_comparison_result) =
+        test_compare_images_with_options("expected-2.png", "actual-2.png", &options)
+    {
+        assert_eq!(
+            image_comparison_result.image_comparison_state,
+            ImageComparisonState::Mismatch
+        );
+        let delta_e: DeltaEStatistics = image_comparison_result
+            .delta_e
+            .expect("delta_e is missing from image_comparison_result");
+        assert!(delta_e.max >= delta_e.mean && delta_e.mean > 0f64);
+        assert_eq!(
+            image_comparison_result.rectangles.len(),
+            image_comparison_result.regions.len()
+        );
+        assert_eq!(
+            image_comparison_result.rectangles.len(),
+            image_comparison_result.region_delta_e.len()
+        );
+        for region_delta_e in image_comparison_result.region_delta_e.iter() {
+            assert!(region_delta_e.max > 2.3);
+            assert!(region_delta_e.mean >= delta_e.mean);
+        }
+        for region in image_comparison_result.regions.iter() {
+            assert!(region.max_color_delta > 2.3);
+            assert!(region.mean_color_delta >= delta_e.mean);
+        }
+    }
+}
+
+#[test]
+fn compare_images_delta_e_below_threshold() {
+    let options: ImageComparisonOptions = ImageComparisonOptions {
+        color_difference: ColorDifference::DeltaE76,
+        delta_e_threshold: 1000f64,
+        ..Default::default()
+    };
+    if let Some(image_comparison_result) =
+        test_compare_images_with_options("expected-2.png", "actual-2.png", &options)
+    {
+        assert_eq!(
+            image_comparison_result.image_comparison_state,
+            ImageComparisonState::Match
+        );
+        assert!(image_comparison_result.delta_e.is_some());
+    }
+}
+
+#[test]
+fn compare_images_regions_sorted_by_severity() {
+    if let Some(image_comparison_result) = test_compare_images_with_options(
+        "expected-4.png",
+        "actual-4.png",
+        &ImageComparisonOptions::default(),
+    ) {
+        let regions: &Vec<Region> = &image_comparison_result.regions;
+        assert!(regions.len() > 1);
+        assert_eq!(image_comparison_result.rectangles.len(), regions.len());
+        for (index, region) in regions.iter().enumerate() {
+            assert!(region
+                .rectangle
+                .equals(&image_comparison_result.rectangles[index]));
+            assert!(region.pixel_count > 0 && region.pixel_count <= region.rectangle.size());
+            assert!(region.centroid.0 >= region.rectangle.min_point.x as f64);
+            assert!(region.centroid.0 <= region.rectangle.max_point.x as f64);
+            assert!(region.centroid.1 >= region.rectangle.min_point.y as f64);
+            assert!(region.centroid.1 <= region.rectangle.max_point.y as f64);
+            assert!(region.max_color_delta >= region.mean_color_delta);
+            if index > 0 {
+                assert!(regions[index - 1].severity_score >= region.severity_score);
+            }
+        }
+    }
+}
+
+#[test]
+fn compare_images_severity_colors() {
+    let options: ImageComparisonOptions = ImageComparisonOptions {
+        severity_colors: true,
+        ..Default::default()
+    };
+    if let Some(image_comparison_result) =
+        test_compare_images_with_options("expected-2.png", "actual-2.png", &options)
+    {
+        let region: Region = image_comparison_result.regions[0].clone();
+        let severity_color: Rgba<u8> = match region.severity {
+            Severity::Low => Rgba::<u8>([255, 215, 0, 255]),
+            Severity::Medium => Rgba::<u8>([255, 140, 0, 255]),
+            Severity::High => Rgba::<u8>([255, 0, 0, 255]),
+        };
+        let result_image: RgbaImage = image_comparison_result
+            .render(RenderStyle::Outline)
+            .expect("result_image is missing from image_comparison_result");
+        assert_eq!(
+            &severity_color,
+            result_image.get_pixel(
+                region.rectangle.min_point.x as u32,
+                region.rectangle.min_point.y as u32
+            )
+        );
+    }
+}
+
+#[test]
+fn compare_images_max_regions_truncated() {
+    match get_tests_resources_directory() {
+        Ok(tests_resources_directory) => {
+            let mut expected: PathBuf = tests_resources_directory.clone();
+            expected.push("expected-4.png");
+            let mut actual: PathBuf = tests_resources_directory.clone();
+            actual.push("actual-4.png");
+            let all_regions: Vec<Region> =
+                compare_images(expected.as_os_str(), actual.as_os_str()).regions;
+            let options: ImageComparisonOptions = ImageComparisonOptions {
+                max_regions: Some(1),
+                ..Default::default()
+            };
+            let image_comparison_result: ImageComparisonResult =
+                compare_images_with_options(expected.as_os_str(), actual.as_os_str(), &options);
+            assert_eq!(
+                image_comparison_result.image_comparison_state,
+                ImageComparisonState::Mismatch
+            );
+            assert!(image_comparison_result.is_truncated);
+            assert_eq!(1, image_comparison_result.regions.len());
+            assert_eq!(1, image_comparison_result.rectangles.len());
+            assert!(all_regions[0]
+                .rectangle
+                .equals(&image_comparison_result.rectangles[0]));
+            assert_eq!(
+                all_regions.len() - 1,
+                image_comparison_result.truncated_region_count
+            );
+            let truncated_area: Rectangle = image_comparison_result
+                .truncated_area
+                .expect("truncated_area is missing from image_comparison_result");
+            for region in all_regions.iter().skip(1) {
+                assert!(truncated_area.contains_point(&region.rectangle.min_point));
+                assert!(truncated_area.contains_point(&region.rectangle.max_point));
+            }
+        }
+        Err(error) => eprintln!("{}", error),
+    }
+}
+
+#[test]
+fn compare_images_max_regions_zero() {
+    let options: ImageComparisonOptions = ImageComparisonOptions {
+        max_regions: Some(0),
+        ..Default::default()
+    };
+    if let Some(image_comparison_result) =
+        test_compare_images_with_options("expected-4.png", "actual-4.png", &options)
+    {
+        assert_eq!(
+            image_comparison_result.image_comparison_state,
+            ImageComparisonState::Mismatch
+        );
+        assert!(image_comparison_result.rectangles.is_empty());
+        assert!(image_comparison_result.is_truncated);
+        assert!(image_comparison_result.truncated_region_count > 0);
+        assert!(image_comparison_result.truncated_area.is_some());
+        assert!(image_comparison_result
+            .render(RenderStyle::Outline)
+            .is_some());
+    }
+}
+
+#[test]
+fn compare_images_max_regions_not_exceeded() {
+    match get_tests_resources_directory() {
+        Ok(tests_resources_directory) => {
+            let mut expected: PathBuf = tests_resources_directory.clone();
+            expected.push("expected-2.png");
+            let mut actual: PathBuf = tests_resources_directory.clone();
+            actual.push("actual-2.png");
+            let options: ImageComparisonOptions = ImageComparisonOptions {
+                max_regions: Some(100),
+                ..Default::default()
+            };
+            let image_comparison_result: ImageComparisonResult =
+                compare_images_with_options(expected.as_os_str(), actual.as_os_str(), &options);
+            assert!(!image_comparison_result.is_truncated);
+            assert_eq!(0, image_comparison_result.truncated_region_count);
+            assert!(image_comparison_result.truncated_area.is_none());
+        }
+        Err(error) => eprintln!("{}", error),
+    }
+}
+
+#[test]
+fn compare_images_merge_options() {
+    match get_tests_resources_directory() {
+        Ok(tests_resources_directory) => {
+            let mut expected: PathBuf = tests_resources_directory.clone();
+            expected.push("expected-6.png");
+            let mut actual: PathBuf = tests_resources_directory.clone();
+            actual.push("actual-6.png");
+            let merged_count: usize = compare_images(expected.as_os_str(), actual.as_os_str())
+                .rectangles
+                .len();
+            let options: ImageComparisonOptions = ImageComparisonOptions {
+                merge_rectangles: false,
+                ..Default::default()
+            };
+            let unmerged_count: usize =
+                compare_images_with_options(expected.as_os_str(), actual.as_os_str(), &options)
+                    .rectangles
+                    .len();
+            assert!(unmerged_count > merged_count);
+            let options: ImageComparisonOptions = ImageComparisonOptions {
+                merge_distance: 1000,
+                ..Default::default()
+            };
+            let image_comparison_result: ImageComparisonResult =
+                compare_images_with_options(expected.as_os_str(), actual.as_os_str(), &options);
+            assert_eq!(1, image_comparison_result.rectangles.len());
+        }
+        Err(error) => eprintln!("{}", error),
+    }
+}
+
+#[test]
+fn compare_images_trace_contours() {
+    match get_tests_resources_directory() {
+        Ok(tests_resources_directory) => {
+            let mut expected: PathBuf = tests_resources_directory.clone();
+            expected.push("expected-4.png");
+            let mut actual: PathBuf = tests_resources_directory.clone();
+            actual.push("actual-4.png");
+            let options: ImageComparisonOptions = ImageComparisonOptions {
+                trace_contours: true,
+                ..Default::default()
+            };
+            let image_comparison_result: ImageComparisonResult =
+                compare_images_with_options(expected.as_os_str(), actual.as_os_str(), &options);
+            assert_eq!(
+                image_comparison_result.image_comparison_state,
+                ImageComparisonState::Mismatch
+            );
+            for region in image_comparison_result.regions.iter() {
+                assert!(!region.contours.is_empty());
+                for contour in region.contours.iter() {
+                    assert!(contour.len() >= 4);
+                    for vertex in contour.iter() {
+                        assert!(vertex.x >= region.rectangle.min_point.x);
+                        assert!(vertex.x <= region.rectangle.max_point.x + 1);
+                        assert!(vertex.y >= region.rectangle.min_point.y);
+                        assert!(vertex.y <= region.rectangle.max_point.y + 1);
+                    }
+                }
+            }
+            let region: &Region = &image_comparison_result.regions[0];
+            let vertex: Point = region.contours[0][0];
+            let result_image: RgbaImage = image_comparison_result
+                .render(RenderStyle::Outline)
+                .expect("result_image is missing from image_comparison_result");
+            assert_eq!(
+                &Rgba::<u8>([255, 0, 0, 255]),
+                result_image.get_pixel(vertex.x as u32, vertex.y as u32)
+            );
+        }
+        Err(error) => eprintln!("{}", error),
+    }
+}
+
+#[test]
+fn compare_images_morphological_operations() {
+    match get_tests_resources_directory() {
+        Ok(tests_resources_directory) => {
+            let mut expected: PathBuf = tests_resources_directory.clone();
+            expected.push("expected-4.png");
+            let mut actual: PathBuf = tests_resources_directory.clone();
+            actual.push("actual-4.png");
+            let image_comparison_result: ImageComparisonResult =
+                compare_images(expected.as_os_str(), actual.as_os_str());
+            let pixel_count: usize = image_comparison_result
+                .regions
+                .iter()
+                .map(|region| region.pixel_count)
+                .sum();
+            let options: ImageComparisonOptions = ImageComparisonOptions {
+                morphological_operations: vec![MorphologicalOperation::Dilation],
+                ..Default::default()
+            };
+            let dilated_result: ImageComparisonResult =
+                compare_images_with_options(expected.as_os_str(), actual.as_os_str(), &options);
+            let dilated_pixel_count: usize = dilated_result
+                .regions
+                .iter()
+                .map(|region| region.pixel_count)
+                .sum();
+            assert!(dilated_pixel_count > pixel_count);
+            assert!(dilated_result.difference_percent > image_comparison_result.difference_percent);
+            let options: ImageComparisonOptions = ImageComparisonOptions {
+                morphological_operations: vec![MorphologicalOperation::Erosion],
+                morphology_kernel_size: 1001,
+                ..Default::default()
+            };
+            let eroded_result: ImageComparisonResult =
+                compare_images_with_options(expected.as_os_str(), actual.as_os_str(), &options);
+            assert_eq!(
+                eroded_result.image_comparison_state,
+                ImageComparisonState::Match
+            );
+            assert!(eroded_result.rectangles.is_empty());
+        }
+        Err(error) => eprintln!("{}", error),
+    }
+}
+
+#[test]
+fn compare_images_region_filters() {
+    match get_tests_resources_directory() {
+        Ok(tests_resources_directory) => {
+            let mut expected: PathBuf = tests_resources_directory.clone();
+            expected.push("expected-4.png");
+            let mut actual: PathBuf = tests_resources_directory.clone();
+            actual.push("actual-4.png");
+            let options: ImageComparisonOptions = ImageComparisonOptions {
+                merge_rectangles: false,
+                ..Default::default()
+            };
+            let regions: Vec<Region> =
+                compare_images_with_options(expected.as_os_str(), actual.as_os_str(), &options)
+                    .regions;
+            let minimal_region_pixel_count: usize = regions[regions.len() / 2].pixel_count;
+            let options: ImageComparisonOptions = ImageComparisonOptions {
+                merge_rectangles: false,
+                minimal_region_pixel_count,
+                ..Default::default()
+            };
+            let filtered_regions: Vec<Region> =
+                compare_images_with_options(expected.as_os_str(), actual.as_os_str(), &options)
+                    .regions;
+            assert_eq!(
+                regions
+                    .iter()
+                    .filter(|region| region.pixel_count >= minimal_region_pixel_count)
+                    .count(),
+                filtered_regions.len()
+            );
+            let options: ImageComparisonOptions = ImageComparisonOptions {
+                merge_rectangles: false,
+                minimal_region_width: 3,
+                minimal_region_height: 3,
+                minimal_region_density: 0.5,
+                ..Default::default()
+            };
+            let filtered_regions: Vec<Region> =
+                compare_images_with_options(expected.as_os_str(), actual.as_os_str(), &options)
+                    .regions;
+            assert!(filtered_regions.len() < regions.len());
+            for region in filtered_regions.iter() {
+                assert!(region.rectangle.get_width() >= 3);
+                assert!(region.rectangle.get_height() >= 3);
+                assert!(region.pixel_count as f64 / region.rectangle.size() as f64 >= 0.5);
+            }
+        }
+        Err(error) => eprintln!("{}", error),
+    }
+}
+
+#[test]
+fn compare_images_tiled_same_rectangles() {
+    match get_tests_resources_directory() {
+        Ok(tests_resources_directory) => {
+            let options: ImageComparisonOptions = ImageComparisonOptions::default();
+            let mut compared_count: usize = 0;
+            for index in 1..=6 {
+                let expected: PathBuf =
+                    tests_resources_directory.join(format!("expected-{}.png", index));
+                let actual: PathBuf =
+                    tests_resources_directory.join(format!("actual-{}.png", index));
+                let tiled_result: ImageComparisonResult = compare_images_tiled(
+                    expected.as_os_str(),
+                    actual.as_os_str(),
+                    None,
+                    7,
+                    &options,
+                );
+                // only 8-bit RGBA images can be compared band by band
+                if tiled_result.image_comparison_state == ImageComparisonState::FormatNotSupported {
+                    continue;
+                }
+                let image_comparison_result: ImageComparisonResult =
+                    compare_images(expected.as_os_str(), actual.as_os_str());
+                assert_eq!(
+                    image_comparison_result.image_comparison_state,
+                    tiled_result.image_comparison_state
+                );
+                assert_eq!(
+                    sorted_coordinates(&image_comparison_result.rectangles),
+                    sorted_coordinates(&tiled_result.rectangles)
+                );
+                compared_count += 1;
+            }
+            assert!(compared_count > 0);
+        }
+        Err(error) => eprintln!("{}", error),
+    }
+}
+
+#[test]
+fn compare_images_tiled_match() {
+    match get_tests_resources_directory() {
+        Ok(tests_resources_directory) => {
+            let mut expected: PathBuf = tests_resources_directory.clone();
+            expected.push("expected_same.png");
+            let mut actual: PathBuf = tests_resources_directory.clone();
+            actual.push("actual_same.png");
+            let image_comparison_result: ImageComparisonResult = compare_images_tiled(
+                expected.as_os_str(),
+                actual.as_os_str(),
+                None,
+                16,
+                &ImageComparisonOptions::default(),
+            );
+            assert_eq!(
+                image_comparison_result.image_comparison_state,
+                ImageComparisonState::Match
+            );
+        }
+        Err(error) => eprintln!("{}", error),
+    }
+}
+
+#[test]
+fn compare_images_tiled_mismatch() {
+    match get_tests_resources_directory() {
+        Ok(tests_resources_directory) => {
+            let mut expected: PathBuf = tests_resources_directory.clone();
+            expected.push("expected-4.png");
+            let mut actual: PathBuf = tests_resources_directory.clone();
+            actual.push("actual-4.png");
+            let mut result: PathBuf = std::env::temp_dir();
+            result.push("image-compare-tiled-result-4.png");
+            let options: ImageComparisonOptions = ImageComparisonOptions::default();
+            // regions crossing the borders of the 7-row bands must be joined
+            let banded_result: ImageComparisonResult = compare_images_tiled(
+                expected.as_os_str(),
+                actual.as_os_str(),
+                Some(result.as_os_str()),
+                7,
+                &options,
+            );
+            let single_band_result: ImageComparisonResult = compare_images_tiled(
+                expected.as_os_str(),
+                actual.as_os_str(),
+                None,
+                u32::MAX,
+                &options,
+            );
+            assert_eq!(
+                banded_result.image_comparison_state,
+                ImageComparisonState::Mismatch
+            );
+            assert!(!banded_result.rectangles.is_empty());
+            assert_eq!(
+                single_band_result.difference_percent,
+                banded_result.difference_percent
+            );
+            assert_eq!(
+                compare_images(expected.as_os_str(), actual.as_os_str()).difference_percent,
+                banded_result.difference_percent
+            );
+            assert_eq!(
+                single_band_result.rectangles.len(),
+                banded_result.rectangles.len()
+            );
+            for (single_band_rectangle, banded_rectangle) in single_band_result
+                .rectangles
+                .iter()
+                .zip(banded_result.rectangles.iter())
+            {
+                assert!(single_band_rectangle.equals(banded_rectangle));
+            }
+            let result_image: RgbaImage = open(&result).unwrap().into_rgba8();
+            let actual_image: RgbaImage = open(&actual).unwrap().into_rgba8();
+            assert_eq!(actual_image.dimensions(), result_image.dimensions());
+            let rectangle: &Rectangle = &banded_result.rectangles[0];
+            assert_eq!(
+                &Rgba::<u8>([255, 0, 0, 255]),
+                result_image.get_pixel(rectangle.min_point.x as u32, rectangle.min_point.y as u32)
+            );
+            // outlined like RenderStyle::Outline, up to 2 pixels outside the rectangle
+            let (width, height) = result_image.dimensions();
+            if rectangle.min_point.x >= 2
+                && rectangle.min_point.y >= 2
+                && rectangle.max_point.x + 2 < width as usize
+                && rectangle.max_point.y + 2 < height as usize
+            {
+                assert_eq!(
+                    &Rgba::<u8>([255, 0, 0, 255]),
+                    result_image.get_pixel(
+                        rectangle.min_point.x as u32 - 2,
+                        rectangle.min_point.y as u32 - 2
+                    )
+                );
+            }
+            std::fs::remove_file(result).unwrap();
+        }
+        Err(error) => eprintln!("{}", error),
+    }
+}
+
+#[test]
+fn images_are_identical_fast_path() {
+    match get_tests_resources_directory() {
+        Ok(tests_resources_directory) => {
+            let mut expected: PathBuf = tests_resources_directory.clone();
+            expected.push("expected-4.png");
+            let mut actual: PathBuf = tests_resources_directory.clone();
+            actual.push("actual-4.png");
+            let options: ImageComparisonOptions = ImageComparisonOptions::default();
+            assert!(images_are_identical(
+                expected.as_os_str(),
+                expected.as_os_str(),
+                &options
+            ));
+            assert!(!images_are_identical(
+                expected.as_os_str(),
+                actual.as_os_str(),
+                &options
+            ));
+            let options: ImageComparisonOptions = ImageComparisonOptions {
+                color_tolerance: 255,
+                ..Default::default()
+            };
+            assert!(images_are_identical(
+                expected.as_os_str(),
+                actual.as_os_str(),
+                &options
+            ));
+            let mut expected: PathBuf = tests_resources_directory.clone();
+            expected.push("expected_same.png");
+            let mut actual: PathBuf = tests_resources_directory.clone();
+            actual.push("actual_same.png");
+            assert!(images_are_identical(
+                expected.as_os_str(),
+                actual.as_os_str(),
+                &ImageComparisonOptions::default()
+            ));
+            let mut expected: PathBuf = tests_resources_directory.clone();
+            expected.push("expected-7.png");
+            let mut actual: PathBuf = tests_resources_directory.clone();
+            actual.push("actual-7.png");
+            assert!(!images_are_identical(
+                expected.as_os_str(),
+                actual.as_os_str(),
+                &ImageComparisonOptions::default()
+            ));
+        }
+        Err(error) => eprintln!("{}", error),
+    }
+}
+
+#[test]
+fn images_are_identical_decodes_rgb_images() {
+    match get_tests_resources_directory() {
+        Ok(tests_resources_directory) => {
+            let mut expected: PathBuf = tests_resources_directory.clone();
+            expected.push("expected-4.png");
+            let mut actual: PathBuf = tests_resources_directory.clone();
+            actual.push("actual-4.png");
+            // RGB images can not be read row by row as RGBA
+            let mut expected_rgb: PathBuf = std::env::temp_dir();
+            expected_rgb.push("image-compare-identical-expected-4-rgb.png");
+            open(&expected)
+                .unwrap()
+                .to_rgb8()
+                .save(&expected_rgb)
+                .unwrap();
+            let options: ImageComparisonOptions = ImageComparisonOptions::default();
+            assert!(images_are_identical(
+                expected_rgb.as_os_str(),
+                expected.as_os_str(),
+                &options
+            ));
+            assert!(!images_are_identical(
+                expected_rgb.as_os_str(),
+                actual.as_os_str(),
+                &options
+            ));
+            std::fs::remove_file(expected_rgb).unwrap();
+        }
+        Err(error) => eprintln!("{}", error),
+    }
+}
+
+#[test]
+fn compare_images_render_styles() {
+    match get_tests_resources_directory() {
+        Ok(tests_resources_directory) => {
+            let mut expected: PathBuf = tests_resources_directory.clone();
+            expected.push("expected-4.png");
+            let mut actual: PathBuf = tests_resources_directory.clone();
+            actual.push("actual-4.png");
+            let image_comparison_result: ImageComparisonResult =
+                compare_images(expected.as_os_str(), actual.as_os_str());
+            let outline: RgbaImage = image_comparison_result
+                .render(RenderStyle::Outline)
+                .expect("outline is missing");
+            let mut result: PathBuf = tests_resources_directory.clone();
+            result.push("result-4.png");
+            assert!(open(result).unwrap().into_rgba8().eq(&outline));
+            let heat_map: RgbaImage = image_comparison_result
+                .render(RenderStyle::HeatMap)
+                .expect("heat map is missing");
+            assert_eq!(outline.dimensions(), heat_map.dimensions());
+            let region: &Region = &image_comparison_result.regions[0];
+            let heat_pixel_count: usize = heat_map
+                .enumerate_pixels()
+                .filter(|(x, y, pixel)| {
+                    region
+                        .rectangle
+                        .contains_point(&Point::new(*x as usize, *y as usize))
+                        && pixel.0[0] == 255
+                        && pixel.0[2] == 0
+                        && pixel.0[1] < 255
+                })
+                .count();
+            assert!(heat_pixel_count > 0);
+            let composite: RgbaImage = image_comparison_result
+                .render(RenderStyle::Composite)
+                .expect("composite is missing");
+            assert_eq!(
+                (outline.width() * 3, outline.height()),
+                composite.dimensions()
+            );
+            let actual_image: RgbaImage = open(&actual).unwrap().into_rgba8();
+            let expected_image: RgbaImage = open(&expected).unwrap().into_rgba8();
+            assert_eq!(expected_image.get_pixel(0, 0), composite.get_pixel(0, 0));
+            assert_eq!(
+                actual_image.get_pixel(0, 0),
+                composite.get_pixel(outline.width(), 0)
+            );
+        }
+        Err(error) => eprintln!("{}", error),
+    }
+}
+
+#[test]
+fn comparator_caches_expected_images() {
+    match get_tests_resources_directory() {
+        Ok(tests_resources_directory) => {
+            let mut expected: PathBuf = tests_resources_directory.clone();
+            expected.push("expected-4.png");
+            let mut actual: PathBuf = tests_resources_directory.clone();
+            actual.push("actual-4.png");
+            let mut same: PathBuf = tests_resources_directory.clone();
+            same.push("actual_same.png");
+            let mut comparator: Comparator = Comparator::new(ImageComparisonOptions::default());
+            let image_comparison_result: ImageComparisonResult =
+                compare_images(expected.as_os_str(), actual.as_os_str());
+            for _ in 0..2 {
+                let comparator_result: ImageComparisonResult =
+                    comparator.compare(expected.as_os_str(), actual.as_os_str());
+                assert_eq!(
+                    comparator_result.image_comparison_state,
+                    ImageComparisonState::Mismatch
+                );
+                assert_eq!(
+                    image_comparison_result.difference_percent,
+                    comparator_result.difference_percent
+                );
+                assert_eq!(
+                    image_comparison_result.rectangles.len(),
+                    comparator_result.rectangles.len()
+                );
+                assert_eq!(1, comparator.cached_image_count());
+            }
+            // the expected image is decoded again when its file changes
+            let mut cached_expected: PathBuf = std::env::temp_dir();
+            cached_expected.push("image-compare-comparator-expected.png");
+            std::fs::copy(&same, &cached_expected).unwrap();
+            assert_eq!(
+                comparator
+                    .compare(cached_expected.as_os_str(), same.as_os_str())
+                    .image_comparison_state,
+                ImageComparisonState::Match
+            );
+            std::fs::copy(&expected, &cached_expected).unwrap();
+            let modified: std::time::SystemTime =
+                std::time::SystemTime::now() + std::time::Duration::from_secs(10);
+            std::fs::File::options()
+                .write(true)
+                .open(&cached_expected)
+                .unwrap()
+                .set_modified(modified)
+                .unwrap();
+            let comparator_result: ImageComparisonResult =
+                comparator.compare(cached_expected.as_os_str(), actual.as_os_str());
+            assert_eq!(
+                image_comparison_result.difference_percent,
+                comparator_result.difference_percent
+            );
+            assert_eq!(2, comparator.cached_image_count());
+            std::fs::remove_file(cached_expected).unwrap();
+        }
+        Err(error) => eprintln!("{}", error),
+    }
+}
+
+#[test]
+fn comparator_cache_capacity_and_undecodable_images() {
+    match get_tests_resources_directory() {
+        Ok(tests_resources_directory) => {
+            let mut expected: PathBuf = tests_resources_directory.clone();
+            expected.push("expected-4.png");
+            let mut same: PathBuf = tests_resources_directory.clone();
+            same.push("expected_same.png");
+            let mut actual_same: PathBuf = tests_resources_directory.clone();
+            actual_same.push("actual_same.png");
+            let mut comparator: Comparator =
+                Comparator::with_cache_capacity(ImageComparisonOptions::default(), 1);
+            comparator.compare(expected.as_os_str(), expected.as_os_str());
+            assert_eq!(
+                comparator
+                    .compare(same.as_os_str(), actual_same.as_os_str())
+                    .image_comparison_state,
+                ImageComparisonState::Match
+            );
+            assert_eq!(1, comparator.cached_image_count());
+            let mut undecodable: PathBuf = std::env::temp_dir();
+            undecodable.push("image-compare-comparator-undecodable.png");
+            std::fs::write(&undecodable, b"not a png image").unwrap();
+            assert_eq!(
+                comparator
+                    .compare(expected.as_os_str(), undecodable.as_os_str())
+                    .image_comparison_state,
+                ImageComparisonState::FormatNotSupported
+            );
+            assert_eq!(
+                comparator
+                    .compare(undecodable.as_os_str(), expected.as_os_str())
+                    .image_comparison_state,
+                ImageComparisonState::FormatNotSupported
+            );
+            std::fs::remove_file(undecodable).unwrap();
+            // paths that are not images at all, with an unknown extension and without one
+            for not_image in ["Cargo.toml", "tests"].iter() {
+                let not_image: PathBuf = PathBuf::from(not_image);
+                assert_eq!(
+                    comparator
+                        .compare(expected.as_os_str(), not_image.as_os_str())
+                        .image_comparison_state,
+                    ImageComparisonState::FormatNotSupported
+                );
+                assert_eq!(
+                    comparator
+                        .compare(not_image.as_os_str(), expected.as_os_str())
+                        .image_comparison_state,
+                    ImageComparisonState::FormatNotSupported
+                );
+            }
+        }
+        Err(error) => eprintln!("{}", error),
+    }
+}
+
+#[test]
+fn compare_images_pixel_comparator() {
+    match get_tests_resources_directory() {
+        Ok(tests_resources_directory) => {
+            let mut expected: PathBuf = tests_resources_directory.clone();
+            expected.push("expected-4.png");
+            let mut actual: PathBuf = tests_resources_directory.clone();
+            actual.push("actual-4.png");
+            let image_comparison_result: ImageComparisonResult =
+                compare_images(expected.as_os_str(), actual.as_os_str());
+            let options: ImageComparisonOptions = ImageComparisonOptions {
+                pixel_comparator: Some(Arc::new(ExactPixelComparator)),
+                ..Default::default()
+            };
+            let exact_result: ImageComparisonResult =
+                compare_images_with_options(expected.as_os_str(), actual.as_os_str(), &options);
+            assert_eq!(
+                image_comparison_result.difference_percent,
+                exact_result.difference_percent
+            );
+            let alpha_only = |expected: &Rgba<u8>, actual: &Rgba<u8>| expected.0[3] != actual.0[3];
+            let pixel_comparator: Arc<dyn PixelComparator> = Arc::new(alpha_only);
+            let options: ImageComparisonOptions = ImageComparisonOptions {
+                pixel_comparator: Some(pixel_comparator),
+                ..Default::default()
+            };
+            let alpha_result: ImageComparisonResult =
+                compare_images_with_options(expected.as_os_str(), actual.as_os_str(), &options);
+            assert!(alpha_result.difference_percent < image_comparison_result.difference_percent);
+        }
+        Err(error) => eprintln!("{}", error),
+    }
+}
+
+#[test]
+fn compare_images_threshold_grouping_filters_before_merging() {
+    let options: ImageComparisonOptions = ImageComparisonOptions {
+        minimal_region_pixel_count: 50,
+        ..Default::default()
+    };
+    let threshold_options: ImageComparisonOptions = ImageComparisonOptions {
+        region_grouping: Some(Arc::new(ThresholdGrouping {
+            threshold: options.threshold,
+            merge_distance: Some(options.merge_distance),
+        })),
+        ..options.clone()
+    };
+    for index in 1..=6 {
+        let expected_image: String = format!("expected-{}.png", index);
+        let actual_image: String = format!("actual-{}.png", index);
+        if let (Some(default_result), Some(threshold_result)) = (
+            test_compare_images_with_options(&expected_image, &actual_image, &options),
+            test_compare_images_with_options(&expected_image, &actual_image, &threshold_options),
+        ) {
+            assert_eq!(
+                default_result.image_comparison_state,
+                threshold_result.image_comparison_state
+            );
+            assert_eq!(
+                sorted_coordinates(&default_result.rectangles),
+                sorted_coordinates(&threshold_result.rectangles)
+            );
+            let pixel_counts = |result: &ImageComparisonResult| -> Vec<usize> {
+                let mut pixel_counts: Vec<usize> = result
+                    .regions
+                    .iter()
+                    .map(|region| region.pixel_count)
+                    .collect();
+                pixel_counts.sort();
+                pixel_counts
+            };
+            assert_eq!(
+                pixel_counts(&default_result),
+                pixel_counts(&threshold_result)
+            );
+        }
+    }
+}
+
+#[test]
+fn compare_images_region_grouping() {
+    match get_tests_resources_directory() {
+        Ok(tests_resources_directory) => {
+            let mut expected: PathBuf = tests_resources_directory.clone();
+            expected.push("expected-4.png");
+            let mut actual: PathBuf = tests_resources_directory.clone();
+            actual.push("actual-4.png");
+            let compare = |region_grouping: Arc<dyn RegionGrouping>| -> Vec<Rectangle> {
+                let options: ImageComparisonOptions = ImageComparisonOptions {
+                    region_grouping: Some(region_grouping),
+                    ..Default::default()
+                };
+                compare_images_with_options(expected.as_os_str(), actual.as_os_str(), &options)
+                    .regions
+                    .into_iter()
+                    .map(|region| region.rectangle)
+                    .collect()
+            };
+            let default_rectangles: Vec<Rectangle> =
+                compare_images(expected.as_os_str(), actual.as_os_str())
+                    .regions
+                    .into_iter()
+                    .map(|region| region.rectangle)
+                    .collect();
+            let threshold_rectangles: Vec<Rectangle> = compare(Arc::new(ThresholdGrouping {
+                threshold: 5,
+                merge_distance: Some(0),
+            }));
+            assert_eq!(default_rectangles.len(), threshold_rectangles.len());
+            for rectangle in default_rectangles.iter() {
+                assert!(threshold_rectangles
+                    .iter()
+                    .any(|threshold_rectangle| threshold_rectangle.equals(rectangle)));
+            }
+            let bounding_box_rectangles: Vec<Rectangle> = compare(Arc::new(BoundingBoxGrouping));
+            assert_eq!(1, bounding_box_rectangles.len());
+            for rectangle in default_rectangles.iter() {
+                assert!(bounding_box_rectangles[0]
+                    .merge(rectangle)
+                    .equals(&bounding_box_rectangles[0]));
+            }
+            let connected_rectangles: Vec<Rectangle> =
+                compare(Arc::new(ConnectedComponentGrouping));
+            assert!(connected_rectangles.len() >= default_rectangles.len());
+            let grid_cell_rectangles: Vec<Rectangle> = compare(Arc::new(GridCellGrouping {
+                cell_width: 16,
+                cell_height: 16,
+            }));
+            assert!(!grid_cell_rectangles.is_empty());
+            for rectangle in grid_cell_rectangles.iter() {
+                assert_eq!(0, rectangle.min_point.x % 16);
+                assert_eq!(0, rectangle.min_point.y % 16);
+            }
+            // every different pixel counts towards one region only
+            let pixel_count = |region_grouping: Arc<dyn RegionGrouping>| -> usize {
+                let options: ImageComparisonOptions = ImageComparisonOptions {
+                    region_grouping: Some(region_grouping),
+                    ..Default::default()
+                };
+                compare_images_with_options(expected.as_os_str(), actual.as_os_str(), &options)
+                    .regions
+                    .iter()
+                    .map(|region| region.pixel_count)
+                    .sum()
+            };
+            let bounding_box_pixel_count: usize = pixel_count(Arc::new(BoundingBoxGrouping));
+            assert!(bounding_box_pixel_count > 0);
+            assert_eq!(
+                bounding_box_pixel_count,
+                pixel_count(Arc::new(ConnectedComponentGrouping))
+            );
+            assert_eq!(
+                bounding_box_pixel_count,
+                pixel_count(Arc::new(GridCellGrouping {
+                    cell_width: 16,
+                    cell_height: 16,
+                }))
+            );
+        }
+        Err(error) => eprintln!("{}", error),
+    }
+}
+
+#[test]
+fn compare_images_grid_summary() {
+    match get_tests_resources_directory() {
+        Ok(tests_resources_directory) => {
+            let mut expected: PathBuf = tests_resources_directory.clone();
+            expected.push("expected-4.png");
+            let mut actual: PathBuf = tests_resources_directory.clone();
+            actual.push("actual-4.png");
+            let options: ImageComparisonOptions = ImageComparisonOptions {
+                grid_size: Some((4, 3)),
+                grid_cell_threshold: 0.01,
+                ..Default::default()
+            };
+            let image_comparison_result: ImageComparisonResult =
+                compare_images_with_options(expected.as_os_str(), actual.as_os_str(), &options);
+            let grid: &DifferenceGrid = image_comparison_result.grid.as_ref().unwrap();
+            let fractions: Vec<Vec<f64>> = grid.fractions();
+            assert_eq!(3, fractions.len());
+            assert!(fractions.iter().all(|row| row.len() == 4));
+            let (width, height) = image_comparison_result.actual_dimensions;
+            let covered_pixels: usize = grid.cells.iter().map(|cell| cell.rectangle.size()).sum();
+            assert_eq!((width * height) as usize, covered_pixels);
+            let different_pixels: f64 = grid
+                .cells
+                .iter()
+                .map(|cell| cell.difference_fraction * cell.rectangle.size() as f64)
+                .sum();
+            let difference_percent: f64 = different_pixels * 100f64 / covered_pixels as f64;
+            assert!((difference_percent - image_comparison_result.difference_percent).abs() < 1e-6);
+            for cell in grid.cells.iter() {
+                assert_eq!(cell.difference_fraction > 0.01, cell.is_highlighted);
+            }
+            assert_eq!(
+                grid.cell(3, 2).unwrap().rectangle.max_point.x,
+                width as usize - 1
+            );
+            assert!(grid.cell(4, 0).is_none());
+            let grid_image: RgbaImage = image_comparison_result.render(RenderStyle::Grid).unwrap();
+            assert_eq!((width, height), grid_image.dimensions());
+            assert!(compare_images(expected.as_os_str(), actual.as_os_str())
+                .grid
+                .is_none());
+        }
+        Err(error) => eprintln!("{}", error),
+    }
+}
+
+#[test]
+fn compare_directories_html_report() {
+    match create_batch_directories("html_report") {
+        Ok((expected_directory, actual_directory)) => {
+            let batch: BatchResult = compare_directories(
+                &expected_directory,
+                &actual_directory,
+                &ImageComparisonOptions::default(),
+                None,
+            )
+            .unwrap();
+            let names: Vec<&str> = batch.pairs.iter().map(|pair| pair.name.as_str()).collect();
+            assert_eq!(vec!["changed.png", "same.png"], names);
+            assert_eq!(1, batch.match_count());
+            assert_eq!(1, batch.mismatch_count());
+            assert!(!batch.is_success());
+            assert_eq!(
+                vec![expected_directory.join("only-expected.png")],
+                batch.missing_actual_images
+            );
+            assert_eq!(
+                vec![actual_directory.join("only-actual.png")],
+                batch.unexpected_actual_images
+            );
+            let report_directory: PathBuf = expected_directory.parent().unwrap().join("report");
+            let report_path: PathBuf = write_html_report(&batch, &report_directory).unwrap();
+            assert_eq!(report_directory.join("index.html"), report_path);
+            let html: String = fs::read_to_string(&report_path).unwrap();
+            assert!(html.contains("2 pairs, 1 passed, 1 failed"));
+            assert!(html.contains("id=\"pair-0\""));
+            assert!(html.contains("overlay-slider"));
+            assert!(html.contains("onion-slider"));
+            assert!(html.contains("only-expected.png"));
+            for image in [
+                "0-expected.png",
+                "0-actual.png",
+                "0-diff.png",
+                "0-thumbnail.png",
+                "1-expected.png",
+                "1-actual.png",
+                "1-thumbnail.png",
+            ] {
+                assert!(html.contains(&format!("images/{}", image)));
+                assert!(report_directory.join("images").join(image).exists());
+            }
+            assert!(!report_directory.join("images").join("1-diff.png").exists());
+        }
+        Err(error) => eprintln!("{}", error),
+    }
+}
+
+#[test]
+fn compare_directories_junit_and_tap_reports() {
+    match create_batch_directories("ci_reports") {
+        Ok((expected_directory, actual_directory)) => {
+            let mut batch: BatchResult = compare_directories(
+                &expected_directory,
+                &actual_directory,
+                &ImageComparisonOptions::default(),
+                None,
+            )
+            .unwrap();
+            let diff_directory: PathBuf = expected_directory.parent().unwrap().join("diff");
+            batch.write_diff_images(&diff_directory).unwrap();
+            let diff_image: PathBuf = diff_directory.join("changed.png");
+            assert_eq!(Some(&diff_image), batch.pairs[0].diff_image.as_ref());
+            assert!(diff_image.exists());
+            assert!(batch.pairs[1].diff_image.is_none());
+            let message: String = format!(
+                "Mismatch: {:.2} % different pixels, {} rectangles",
+                batch.pairs[0].result.difference_percent,
+                batch.pairs[0].result.rectangles.len()
+            );
+            let junit: String = junit_report(&batch);
+            assert!(junit.contains("<testsuites tests=\"4\" failures=\"3\">"));
+            assert!(junit.contains("<testcase classname=\"image_compare\" name=\"same.png\">"));
+            assert!(junit.contains(&format!(
+                "<failure type=\"Mismatch\" message=\"{}\"/>",
+                message
+            )));
+            assert!(junit.contains(&format!(
+                "<property name=\"diff_image\" value=\"{}\"/>",
+                diff_image.display()
+            )));
+            assert_eq!(3, junit.matches("<failure ").count());
+            let tap: String = tap_report(&batch);
+            assert!(tap.starts_with("TAP version 13\n1..4\n"));
+            assert!(tap.contains("not ok 1 - changed.png\n"));
+            assert!(tap.contains(&format!("  message: \"{}\"\n", message)));
+            assert!(tap.contains(&format!("  diff_image: \"{}\"\n", diff_image.display())));
+            assert!(tap.contains("ok 2 - same.png\n"));
+            assert!(tap.contains("not ok 3 - only-expected.png\n"));
+            assert!(tap.contains("not ok 4 - only-actual.png\n"));
+        }
+        Err(error) => eprintln!("{}", error),
+    }
+}
+
+#[test]
+fn compare_directories_markdown_report() {
+    match create_batch_directories("markdown") {
+        Ok((expected_directory, actual_directory)) => {
+            let batch_directory: PathBuf = expected_directory.parent().unwrap().to_path_buf();
+            // the diff images are written while comparing and the pairs drop their images
+            let batch: BatchResult = compare_directories(
+                &expected_directory,
+                &actual_directory,
+                &ImageComparisonOptions::default(),
+                Some(&batch_directory.join("diff")),
+            )
+            .unwrap();
+            assert!(batch.pairs[0].result.render(RenderStyle::Outline).is_none());
+            let report_path: PathBuf =
+                write_html_report(&batch, &batch_directory.join("html")).unwrap();
+            assert!(fs::read_to_string(report_path)
+                .unwrap()
+                .contains("images/0-diff.png"));
+            let markdown: String = markdown_report(&batch, &batch_directory.join("report"));
+            assert!(markdown.contains("**Failed**: 2 pairs, 1 passed, 1 failed"));
+            assert!(markdown.contains("| changed.png | Mismatch |"));
+            assert!(!markdown.contains("| same.png |"));
+            assert_eq!(1, markdown.matches("<details>").count());
+            assert!(markdown.contains("![diff of changed.png](../diff/changed.png)"));
+            assert!(markdown.contains("only-expected.png"));
+            assert!(markdown.contains("only-actual.png"));
+            let pair: ComparisonPair = ComparisonPair::compare(
+                &expected_directory.join("same.png"),
+                &actual_directory.join("same.png"),
+                &ImageComparisonOptions::default(),
+            );
+            let markdown: String = markdown_pair_report(&pair, &batch_directory);
+            assert!(markdown.contains("**Passed**: `same.png` matches"));
+            let markdown: String = markdown_pair_report(&batch.pairs[0], &batch_directory);
+            assert!(markdown.contains("**Failed**: Mismatch: "));
+            assert!(markdown.contains("](diff/changed.png)"));
+        }
+        Err(error) => eprintln!("{}", error),
+    }
+}
+
+// compares two images of the tests resources directory, None without the directory
+fn test_compare_images_with_options(
+    expected_image: &str,
+    actual_image: &str,
+    options: &ImageComparisonOptions,
+) -> Option<ImageComparisonResult> {
+    match get_tests_resources_directory() {
+        Ok(tests_resources_directory) => {
+            let mut expected: PathBuf = tests_resources_directory.clone();
+            expected.push(expected_image);
+            let mut actual: PathBuf = tests_resources_directory.clone();
+            actual.push(actual_image);
+            Some(compare_images_with_options(
+                expected.as_os_str(),
+                actual.as_os_str(),
+                options,
+            ))
+        }
+        Err(error) => {
+            eprintln!("{}", error);
+            None
+        }
+    }
+}
+
+// the rectangles of compare_images are ordered by severity
+fn sorted_coordinates(rectangles: &[Rectangle]) -> Vec<(usize, usize, usize, usize)> {
+    let mut coordinates: Vec<(usize, usize, usize, usize)> = rectangles
+        .iter()
+        .map(|rectangle| {
+            (
+                rectangle.min_point.x,
+                rectangle.min_point.y,
+                rectangle.max_point.x,
+                rectangle.max_point.y,
+            )
+        })
+        .collect();
+    coordinates.sort();
+    coordinates
+}
+
 fn test_compare_images_match(expected_image: &str, actual_image: &str) {
     match get_tests_resources_directory() {
         Ok(tests_resources_directory) => {
@@ -110,7 +1718,9 @@ fn test_compare_images_match(expected_image: &str, actual_image: &str) {
                 image_comparison_result.image_comparison_state,
                 ImageComparisonState::Match
             );
-            assert_eq!(None, image_comparison_result.result_image);
+            assert!(image_comparison_result
+                .render(RenderStyle::Outline)
+                .is_none());
         }
         Err(error) => eprintln!("{}", error),
     }
@@ -129,15 +1739,34 @@ fn test_compare_images_mismatch(expected_image: &str, actual_image: &str, result
                 image_comparison_result.image_comparison_state,
                 ImageComparisonState::Mismatch
             );
-            assert_ne!(None, image_comparison_result.result_image);
+            assert!(image_comparison_result
+                .render(RenderStyle::Outline)
+                .is_some());
             let mut result: PathBuf = tests_resources_directory.clone();
             result.push(result_image);
             let expected_result: RgbaImage = open(result).unwrap().into_rgba8();
             let actual_result: RgbaImage = image_comparison_result
-                .result_image
+                .render(RenderStyle::Outline)
                 .expect("result_image is missing from image_comparison_result");
-            assert_eq!(true, expected_result.eq(&actual_result));
+            assert!(expected_result.eq(&actual_result));
         }
         Err(error) => eprintln!("{}", error),
     }
 }
+
+#[test]
+fn command_line_rejects_non_images() {
+    let mut without_extension: PathBuf = std::env::temp_dir();
+    without_extension.push("image-compare-without-extension");
+    fs::write(&without_extension, b"not an image").unwrap();
+    for not_image in [PathBuf::from("Cargo.toml"), without_extension.clone()].iter() {
+        let output: Output = Command::new(env!("CARGO_BIN_EXE_image_compare"))
+            .arg(not_image)
+            .arg(not_image)
+            .output()
+            .unwrap();
+        assert_eq!(Some(2), output.status.code());
+        assert!(!String::from_utf8_lossy(&output.stderr).contains("panicked"));
+    }
+    fs::remove_file(without_extension).unwrap();
+}